@@ -0,0 +1,60 @@
+//! A recorded set of known duplicates, so that only new ones need attention.
+
+use crate::error::Result;
+use crate::Duplicate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// One duplicated `(package, version)` pair.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub package: String,
+    pub version: String,
+}
+
+impl BaselineEntry {
+    fn of(duplicate: &Duplicate) -> Self {
+        BaselineEntry {
+            package: duplicate.package.clone(),
+            version: duplicate.version.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub duplicates: BTreeSet<BaselineEntry>,
+}
+
+impl Baseline {
+    pub fn from_duplicates(duplicates: &[Duplicate]) -> Self {
+        Baseline {
+            duplicates: duplicates.iter().map(BaselineEntry::of).collect(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(std::fs::write(path, json)?)
+    }
+
+    pub fn contains(&self, duplicate: &Duplicate) -> bool {
+        self.duplicates.contains(&BaselineEntry::of(duplicate))
+    }
+
+    /// Entries that are no longer among `duplicates`, and can be dropped from the
+    /// baseline.
+    pub fn fixed(&self, duplicates: &[Duplicate]) -> Vec<BaselineEntry> {
+        let current = Baseline::from_duplicates(duplicates);
+        self.duplicates
+            .difference(&current.duplicates)
+            .cloned()
+            .collect()
+    }
+}
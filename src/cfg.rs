@@ -0,0 +1,338 @@
+//! Evaluation of the `cfg(...)` platform conditions found on dependency edges.
+
+use crate::error::{Error, Result};
+use std::process::Command;
+use std::str::FromStr;
+
+/// A single configuration value, such as `unix` or `target_os = "linux"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Cfg {
+    Name(String),
+    KeyPair(String, String),
+}
+
+impl FromStr for Cfg {
+    type Err = Error;
+
+    /// Parses one line of `rustc --print cfg` output.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((key, value)) => {
+                let value = value
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .ok_or_else(|| Error::Cfg(format!("malformed cfg value `{s}`")))?;
+                Ok(Cfg::KeyPair(key.trim().to_string(), value.to_string()))
+            }
+            None => Ok(Cfg::Name(s.trim().to_string())),
+        }
+    }
+}
+
+/// A parsed `cfg(...)` expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CfgExpr {
+    Not(Box<CfgExpr>),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Value(Cfg),
+}
+
+impl CfgExpr {
+    pub fn matches(&self, cfg: &[Cfg]) -> bool {
+        match self {
+            CfgExpr::Not(expr) => !expr.matches(cfg),
+            CfgExpr::All(exprs) => exprs.iter().all(|expr| expr.matches(cfg)),
+            CfgExpr::Any(exprs) => exprs.iter().any(|expr| expr.matches(cfg)),
+            CfgExpr::Value(value) => cfg.contains(value),
+        }
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = Error;
+
+    /// Parses the contents of a `cfg(...)`, without the surrounding `cfg()`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.position != parser.tokens.len() {
+            return Err(Error::Cfg(format!("unexpected trailing input in `{s}`")));
+        }
+        Ok(expr)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+            ',' => tokens.push(Token::Comma),
+            '=' => tokens.push(Token::Equals),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(Error::Cfg(format!("unterminated string in `{s}`"))),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            c if c.is_whitespace() => {}
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => return Err(Error::Cfg(format!("unexpected `{c}` in `{s}`"))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(Error::Cfg(format!(
+                "expected {expected:?}, found {token:?}"
+            ))),
+        }
+    }
+
+    fn expr(&mut self) -> Result<CfgExpr> {
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            token => return Err(Error::Cfg(format!("expected identifier, found {token:?}"))),
+        };
+        match (name.as_str(), self.peek()) {
+            ("not", Some(Token::LeftParen)) => {
+                self.expect(Token::LeftParen)?;
+                let expr = self.expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(CfgExpr::Not(Box::new(expr)))
+            }
+            ("all", Some(Token::LeftParen)) => Ok(CfgExpr::All(self.list()?)),
+            ("any", Some(Token::LeftParen)) => Ok(CfgExpr::Any(self.list()?)),
+            (_, Some(Token::Equals)) => {
+                self.next();
+                match self.next() {
+                    Some(Token::String(value)) => Ok(CfgExpr::Value(Cfg::KeyPair(name, value))),
+                    token => Err(Error::Cfg(format!("expected string, found {token:?}"))),
+                }
+            }
+            _ => Ok(CfgExpr::Value(Cfg::Name(name))),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<CfgExpr>> {
+        self.expect(Token::LeftParen)?;
+        let mut exprs = vec![];
+        loop {
+            if let Some(Token::RightParen) = self.peek() {
+                self.next();
+                return Ok(exprs);
+            }
+            exprs.push(self.expr()?);
+            match self.next() {
+                Some(Token::Comma) => {}
+                Some(Token::RightParen) => return Ok(exprs),
+                token => return Err(Error::Cfg(format!("expected `,` or `)`, found {token:?}"))),
+            }
+        }
+    }
+}
+
+/// A compilation target and the cfg values `rustc` reports for it.
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub triple: String,
+    pub cfg: Vec<Cfg>,
+}
+
+impl Target {
+    /// Asks `rustc` for the cfg values of `triple`, or of the host when `None`.
+    pub fn from_rustc(triple: Option<&str>) -> Result<Self> {
+        let triple = match triple {
+            Some(triple) => triple.to_string(),
+            None => host_triple()?,
+        };
+        let output = rustc(&["--print", "cfg", "--target", &triple])?;
+        let cfg = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Cfg::from_str)
+            .collect::<Result<_>>()?;
+        Ok(Target { triple, cfg })
+    }
+
+    /// Checks a dependency's platform specification, which is either a
+    /// `cfg(...)` expression or a literal target triple.
+    pub fn matches(&self, platform: &str) -> Result<bool> {
+        match platform
+            .strip_prefix("cfg(")
+            .and_then(|expr| expr.strip_suffix(')'))
+        {
+            Some(expr) => Ok(CfgExpr::from_str(expr)?.matches(&self.cfg)),
+            None => Ok(platform == self.triple),
+        }
+    }
+}
+
+fn host_triple() -> Result<String> {
+    rustc(&["-vV"])?
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| host.trim().to_string())
+        .ok_or_else(|| Error::Cfg("rustc did not report a host triple".to_string()))
+}
+
+fn rustc(args: &[&str]) -> Result<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::Command {
+            program: "rustc".to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux() -> Target {
+        Target {
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+            cfg: [
+                "unix",
+                "debug_assertions",
+                "target_os=\"linux\"",
+                "target_arch=\"x86_64\"",
+                "target_family=\"unix\"",
+            ]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect(),
+        }
+    }
+
+    fn matches(platform: &str) -> bool {
+        linux().matches(platform).unwrap()
+    }
+
+    #[test]
+    fn parses_rustc_cfg_lines() {
+        assert_eq!(
+            "unix".parse::<Cfg>().unwrap(),
+            Cfg::Name("unix".to_string())
+        );
+        assert_eq!(
+            "target_os=\"linux\"".parse::<Cfg>().unwrap(),
+            Cfg::KeyPair("target_os".to_string(), "linux".to_string())
+        );
+        assert!("target_os=linux".parse::<Cfg>().is_err());
+    }
+
+    #[test]
+    fn names_and_key_pairs() {
+        assert!(matches("cfg(unix)"));
+        assert!(!matches("cfg(windows)"));
+        assert!(matches("cfg(target_os = \"linux\")"));
+        assert!(!matches("cfg(target_os = \"macos\")"));
+        // A key only matches as a pair, not as a bare name
+        assert!(!matches("cfg(target_os)"));
+    }
+
+    #[test]
+    fn nested_operators() {
+        assert!(matches("cfg(not(windows))"));
+        assert!(!matches("cfg(not(unix))"));
+        assert!(matches("cfg(all(unix, target_arch = \"x86_64\"))"));
+        assert!(!matches("cfg(all(unix, target_arch = \"aarch64\"))"));
+        assert!(matches("cfg(any(windows, target_os = \"linux\"))"));
+        assert!(matches(
+            "cfg(all(not(windows), any(target_os = \"macos\", target_os = \"linux\")))"
+        ));
+        assert!(!matches(
+            "cfg(not(all(unix, any(debug_assertions, target_os = \"macos\"))))"
+        ));
+        assert!(matches("cfg(any(windows, unix,))"));
+    }
+
+    #[test]
+    fn empty_lists() {
+        assert_eq!("all()".parse::<CfgExpr>().unwrap(), CfgExpr::All(vec![]));
+        assert!(matches("cfg(all())"));
+        assert!(!matches("cfg(any())"));
+    }
+
+    #[test]
+    fn literal_triples() {
+        assert!(matches("x86_64-unknown-linux-gnu"));
+        assert!(!matches("x86_64-pc-windows-msvc"));
+    }
+
+    #[test]
+    fn malformed_input() {
+        for expr in [
+            "",
+            "not(unix",
+            "not()",
+            "all(unix,,windows)",
+            "all(unix windows)",
+            "target_os =",
+            "target_os = linux",
+            "target_os = \"linux",
+            "unix windows",
+            "unix)",
+            "foo-bar",
+            "(unix)",
+        ] {
+            assert!(expr.parse::<CfgExpr>().is_err(), "`{expr}` parsed");
+        }
+        assert!(linux().matches("cfg(not(unix)").is_err());
+    }
+}
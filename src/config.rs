@@ -0,0 +1,327 @@
+//! Settings read from the `[workspace.metadata.duplicated-deps]` and
+//! `[package.metadata.duplicated-deps]` tables of `Cargo.toml`.
+
+use crate::error::{Error, Result};
+use crate::Duplicate;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An accepted duplicate, written either as a bare spec or as a table:
+///
+/// ```toml
+/// [workspace.metadata.duplicated-deps]
+/// ignore = [
+///     "windows-sys",
+///     { crate = "syn@1", reason = "waiting for the syn 2 migration", until = "2025-06-30" },
+/// ]
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IgnoreEntry {
+    Spec(String),
+    Detailed {
+        #[serde(rename = "crate")]
+        spec: String,
+        reason: Option<String>,
+        /// The last day the entry applies, as `YYYY-MM-DD`.
+        until: Option<String>,
+    },
+}
+
+impl IgnoreEntry {
+    /// The `name` or `name@version` this entry matches, where `version` may leave
+    /// out trailing components, as in `syn@1` or `windows-sys@0.52`.
+    pub fn spec(&self) -> &str {
+        match self {
+            IgnoreEntry::Spec(spec) | IgnoreEntry::Detailed { spec, .. } => spec,
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            IgnoreEntry::Spec(_) => None,
+            IgnoreEntry::Detailed { reason, .. } => reason.as_deref(),
+        }
+    }
+
+    pub fn until(&self) -> Option<&str> {
+        match self {
+            IgnoreEntry::Spec(_) => None,
+            IgnoreEntry::Detailed { until, .. } => until.as_deref(),
+        }
+    }
+
+    /// Whether the entry has passed its `until` date on `today`. Both are
+    /// `YYYY-MM-DD`, so they compare as strings.
+    pub fn is_expired(&self, today: &str) -> bool {
+        self.until().is_some_and(|until| until < today)
+    }
+
+    pub fn matches(&self, duplicate: &Duplicate) -> bool {
+        let (name, version) = match self.spec().split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (self.spec(), None),
+        };
+        name == duplicate.package
+            && version.is_none_or(|version| {
+                let mut actual = duplicate.version.split(['.', '-', '+']);
+                version
+                    .split('.')
+                    .all(|component| actual.next() == Some(component))
+            })
+    }
+}
+
+/// A duplicate left out of the report by an [`IgnoreEntry`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Suppressed {
+    pub package: String,
+    pub version: String,
+    /// The spec of the entry that matched.
+    pub ignored_by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub ignore: Vec<IgnoreEntry>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    workspace: Option<Section>,
+    package: Option<Section>,
+}
+
+#[derive(Deserialize)]
+struct Section {
+    metadata: Option<MetadataTable>,
+}
+
+#[derive(Deserialize)]
+struct MetadataTable {
+    #[serde(rename = "duplicated-deps")]
+    duplicated_deps: Option<Config>,
+}
+
+impl Config {
+    /// Reads the settings from a `Cargo.toml`, combining the workspace and package
+    /// tables. A missing manifest gives the default settings.
+    pub fn load(manifest_path: impl AsRef<Path>) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref();
+        let Ok(manifest) = std::fs::read_to_string(manifest_path) else {
+            return Ok(Config::default());
+        };
+        let manifest: Manifest = toml::from_str(&manifest).map_err(|e| {
+            Error::Manifest(format!("failed to parse {}: {e}", manifest_path.display()))
+        })?;
+        let mut config = Config::default();
+        for section in [manifest.workspace, manifest.package].into_iter().flatten() {
+            if let Some(table) = section
+                .metadata
+                .and_then(|metadata| metadata.duplicated_deps)
+            {
+                config.ignore.extend(table.ignore);
+            }
+        }
+        for entry in &config.ignore {
+            if let Some(until) = entry.until() {
+                if !is_date(until) {
+                    return Err(Error::Manifest(format!(
+                        "ignore entry `{}` has until = \"{until}\", expected YYYY-MM-DD",
+                        entry.spec()
+                    )));
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Splits `duplicates` into the ones still reported and the ones an unexpired
+    /// ignore entry suppresses on `today`.
+    pub fn apply(
+        &self,
+        duplicates: Vec<Duplicate>,
+        today: &str,
+    ) -> (Vec<Duplicate>, Vec<Suppressed>) {
+        let mut reported = vec![];
+        let mut suppressed = vec![];
+        for duplicate in duplicates {
+            let entry = self
+                .ignore
+                .iter()
+                .find(|entry| !entry.is_expired(today) && entry.matches(&duplicate));
+            match entry {
+                Some(entry) => suppressed.push(Suppressed {
+                    package: duplicate.package,
+                    version: duplicate.version,
+                    ignored_by: entry.spec().to_string(),
+                    reason: entry.reason().map(String::from),
+                    until: entry.until().map(String::from),
+                }),
+                None => reported.push(duplicate),
+            }
+        }
+        (reported, suppressed)
+    }
+
+    /// Entries whose `until` date has passed on `today`.
+    pub fn expired<'a>(&'a self, today: &'a str) -> impl Iterator<Item = &'a IgnoreEntry> {
+        self.ignore
+            .iter()
+            .filter(move |entry| entry.is_expired(today))
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    // Converts days since 1970-01-01 to a civil date, after Howard Hinnant's
+    // `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn duplicate(package: &str, version: &str) -> Duplicate {
+        serde_json::from_value(json!({
+            "package": package,
+            "version": version,
+            "source": null,
+            "kind": "major",
+            "canonical": null,
+            "latest": version,
+            "users": [],
+        }))
+        .unwrap()
+    }
+
+    fn matches(spec: &str, package: &str, version: &str) -> bool {
+        IgnoreEntry::Spec(spec.to_string()).matches(&duplicate(package, version))
+    }
+
+    #[test]
+    fn matches_whole_version_components() {
+        assert!(matches("syn", "syn", "1.0.109"));
+        assert!(matches("syn@1", "syn", "1.0.109"));
+        assert!(matches("syn@1.0.109", "syn", "1.0.109"));
+        assert!(!matches("syn@1", "syn", "10.0.0"));
+        assert!(!matches("syn@1.0", "syn", "1.1.0"));
+        assert!(!matches("syn@1.0.109.1", "syn", "1.0.109"));
+        assert!(!matches("syn", "syn-mid", "1.0.0"));
+    }
+
+    #[test]
+    fn matches_prerelease_and_build_versions_by_their_numbers() {
+        assert!(matches(
+            "wasi@0.11",
+            "wasi",
+            "0.11.0+wasi-snapshot-preview1"
+        ));
+        assert!(matches("foo@2.0.0", "foo", "2.0.0-rc.1"));
+    }
+
+    #[test]
+    fn expired_entries_no_longer_apply() {
+        let config = Config {
+            ignore: vec![
+                IgnoreEntry::Detailed {
+                    spec: "syn@1".to_string(),
+                    reason: Some("waiting for syn 2".to_string()),
+                    until: Some("2025-06-30".to_string()),
+                },
+                IgnoreEntry::Spec("windows-sys".to_string()),
+            ],
+        };
+        let duplicates = vec![
+            duplicate("syn", "1.0.109"),
+            duplicate("windows-sys", "0.52.0"),
+        ];
+
+        let (reported, suppressed) = config.apply(duplicates.clone(), "2025-06-30");
+        assert!(reported.is_empty());
+        assert_eq!(suppressed[0].reason.as_deref(), Some("waiting for syn 2"));
+
+        let (reported, suppressed) = config.apply(duplicates, "2025-07-01");
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].package, "syn");
+        assert_eq!(suppressed[0].ignored_by, "windows-sys");
+        assert_eq!(config.expired("2025-07-01").count(), 1);
+    }
+
+    fn load(test: &str, manifest: &str) -> Result<Config> {
+        let dir = std::env::temp_dir().join(format!(
+            "cargo-duplicated-deps-{test}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        let config = Config::load(dir.join("Cargo.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        config
+    }
+
+    #[test]
+    fn loads_workspace_and_package_tables() {
+        let config = load(
+            "config-tables",
+            r#"
+[workspace.metadata.duplicated-deps]
+ignore = [{ crate = "syn@1", until = "2025-06-30" }]
+
+[package.metadata.duplicated-deps]
+ignore = ["windows-sys"]
+"#,
+        )
+        .unwrap();
+        let specs: Vec<&str> = config.ignore.iter().map(IgnoreEntry::spec).collect();
+        assert_eq!(specs, ["syn@1", "windows-sys"]);
+    }
+
+    #[test]
+    fn rejects_malformed_until_dates() {
+        for until in ["2025-6-30", "30/06/2025", "2025-06-30T00:00", "tomorrow"] {
+            let manifest = format!(
+                "[workspace.metadata.duplicated-deps]\nignore = [{{ crate = \"syn\", until = \"{until}\" }}]\n"
+            );
+            assert!(
+                matches!(load("config-dates", &manifest), Err(Error::Manifest(_))),
+                "{until}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_manifest_gives_the_default() {
+        let config = Config::load("/nonexistent/Cargo.toml").unwrap();
+        assert!(config.ignore.is_empty());
+    }
+}
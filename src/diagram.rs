@@ -0,0 +1,127 @@
+//! GraphViz DOT and Mermaid diagrams explaining why duplicates are in the graph.
+
+use crate::graph::{find_info, PackageMap};
+use crate::Duplicate;
+use cargo_lock::Dependency;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+/// Fill colours for duplicated crates, reused when there are more crates than colours.
+const PALETTE: [&str; 8] = [
+    "#8dd3c7", "#ffffb3", "#bebada", "#fb8072", "#80b1d3", "#fdb462", "#b3de69", "#fccde5",
+];
+
+/// The part of the dependency graph that explains a set of duplicates: every copy of
+/// each duplicated crate, and every package on a reverse path from those copies up to
+/// a workspace member.
+#[derive(Clone, Debug, Default)]
+pub struct Diagram {
+    /// Each package, with the palette colour of its crate when it is duplicated.
+    pub nodes: BTreeMap<Dependency, Option<&'static str>>,
+    /// `(user, dependency)` edges.
+    pub edges: BTreeSet<(Dependency, Dependency)>,
+}
+
+impl Diagram {
+    pub fn explain(duplicates: &[Duplicate], package_map: &PackageMap) -> Self {
+        let crates: BTreeSet<&str> = duplicates
+            .iter()
+            .map(|duplicate| duplicate.package.as_str())
+            .collect();
+        let mut diagram = Diagram::default();
+        let mut queue = VecDeque::new();
+        for (i, name) in crates.into_iter().enumerate() {
+            for info in package_map.get(name).into_iter().flatten() {
+                diagram
+                    .nodes
+                    .insert(info.id.clone(), Some(PALETTE[i % PALETTE.len()]));
+                queue.push_back(info.id.clone());
+            }
+        }
+        // Walk the reverse edges up to the workspace members
+        while let Some(id) = queue.pop_front() {
+            if id.source.is_none() {
+                continue;
+            }
+            let Some(info) = find_info(package_map, &id) else {
+                continue;
+            };
+            for user in &info.users {
+                let user = Dependency::from(user);
+                diagram.edges.insert((user.clone(), id.clone()));
+                if !diagram.nodes.contains_key(&user) {
+                    diagram.nodes.insert(user.clone(), None);
+                    queue.push_back(user);
+                }
+            }
+        }
+        diagram
+    }
+
+    /// `name vX`, followed by the source for packages that are not from crates.io.
+    /// Workspace members are drawn differently, so only duplicated path packages are
+    /// marked as such.
+    fn label(&self, id: &Dependency) -> String {
+        match &id.source {
+            Some(source) if !source.is_default_registry() => {
+                format!("{} v{} ({source})", id.name, id.version)
+            }
+            None if self.nodes.get(id).is_some_and(Option::is_some) => {
+                format!("{} v{} (path)", id.name, id.version)
+            }
+            _ => format!("{} v{}", id.name, id.version),
+        }
+    }
+
+    /// Short node names, in node order.
+    fn ids(&self) -> BTreeMap<&Dependency, String> {
+        self.nodes
+            .keys()
+            .enumerate()
+            .map(|(i, id)| (id, format!("n{i}")))
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let ids = self.ids();
+        let mut out = String::from("digraph duplicates {\n");
+        out.push_str("    rankdir=LR;\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (id, color) in &self.nodes {
+            let style = match (color, id.source.is_none()) {
+                (Some(color), _) => format!(", style=filled, fillcolor=\"{color}\""),
+                (None, true) => ", style=bold".to_string(),
+                (None, false) => String::new(),
+            };
+            _ = writeln!(
+                out,
+                "    {} [label=\"{}\"{style}];",
+                ids[id],
+                self.label(id)
+            );
+        }
+        for (user, dependency) in &self.edges {
+            _ = writeln!(out, "    {} -> {};", ids[user], ids[dependency]);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let ids = self.ids();
+        let mut out = String::from("graph LR\n");
+        for id in self.nodes.keys() {
+            _ = writeln!(out, "    {}[\"{}\"]", ids[id], self.label(id));
+        }
+        for (user, dependency) in &self.edges {
+            _ = writeln!(out, "    {} --> {}", ids[user], ids[dependency]);
+        }
+        for (id, color) in &self.nodes {
+            match (color, id.source.is_none()) {
+                (Some(color), _) => _ = writeln!(out, "    style {} fill:{color}", ids[id]),
+                (None, true) => _ = writeln!(out, "    style {} stroke-width:3px", ids[id]),
+                (None, false) => {}
+            }
+        }
+        out
+    }
+}
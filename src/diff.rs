@@ -0,0 +1,243 @@
+//! Compares the duplicates found in two versions of a dependency graph.
+
+use crate::Duplicate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A duplicate found on both sides that differs between them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Changed {
+    pub before: Duplicate,
+    pub after: Duplicate,
+    /// What differs, such as `version 1.0.0 -> 1.1.0` or `new user foo 0.2.0`.
+    pub changes: Vec<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateDiff {
+    /// Duplicates only found on the new side.
+    pub added: Vec<Duplicate>,
+    /// Duplicates only found on the old side.
+    pub removed: Vec<Duplicate>,
+    pub changed: Vec<Changed>,
+}
+
+impl DuplicateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn user_names(duplicate: &Duplicate) -> BTreeSet<String> {
+    duplicate
+        .users
+        .iter()
+        .map(|user| format!("{} {}", user.name, user.version))
+        .collect()
+}
+
+fn describe_changes(before: &Duplicate, after: &Duplicate) -> Vec<String> {
+    let mut changes = vec![];
+    if before.version != after.version {
+        changes.push(format!("version {} -> {}", before.version, after.version));
+    }
+    if before.source != after.source {
+        let source = |duplicate: &Duplicate| match &duplicate.source {
+            Some(source) => source.to_string(),
+            None => "path".to_string(),
+        };
+        changes.push(format!("source {} -> {}", source(before), source(after)));
+    }
+    if before.kind != after.kind {
+        changes.push(format!("kind {} -> {}", before.kind, after.kind));
+    }
+    if before.canonical != after.canonical {
+        let canonical = |duplicate: &Duplicate| duplicate.canonical.clone().unwrap_or_default();
+        changes.push(format!(
+            "canonical {} -> {}",
+            canonical(before),
+            canonical(after)
+        ));
+    }
+    let (old_users, new_users) = (user_names(before), user_names(after));
+    for user in new_users.difference(&old_users) {
+        changes.push(format!("new user {user}"));
+    }
+    for user in old_users.difference(&new_users) {
+        changes.push(format!("no longer used by {user}"));
+    }
+    changes
+}
+
+/// Matches the duplicates of each crate on both sides. Copies with the same version
+/// and source are compared directly, and leftover copies of the same crate are
+/// paired up as a version change. Everything else was added or removed.
+pub fn diff_duplicates(old: Vec<Duplicate>, new: Vec<Duplicate>) -> DuplicateDiff {
+    let mut by_package: BTreeMap<String, (Vec<Duplicate>, Vec<Duplicate>)> = BTreeMap::new();
+    for duplicate in old {
+        by_package
+            .entry(duplicate.package.clone())
+            .or_default()
+            .0
+            .push(duplicate);
+    }
+    for duplicate in new {
+        by_package
+            .entry(duplicate.package.clone())
+            .or_default()
+            .1
+            .push(duplicate);
+    }
+
+    let mut diff = DuplicateDiff::default();
+    for (_, (old, mut new)) in by_package {
+        let mut unmatched = vec![];
+        for before in old {
+            let same = new
+                .iter()
+                .position(|after| after.version == before.version && after.source == before.source);
+            match same {
+                Some(position) => {
+                    let after = new.remove(position);
+                    let changes = describe_changes(&before, &after);
+                    if !changes.is_empty() {
+                        diff.changed.push(Changed {
+                            before,
+                            after,
+                            changes,
+                        });
+                    }
+                }
+                None => unmatched.push(before),
+            }
+        }
+        let mut new = new.into_iter();
+        for before in unmatched {
+            match new.next() {
+                Some(after) => diff.changed.push(Changed {
+                    changes: describe_changes(&before, &after),
+                    before,
+                    after,
+                }),
+                None => diff.removed.push(before),
+            }
+        }
+        diff.added.extend(new);
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo_lock::Package;
+    use serde_json::json;
+
+    fn duplicate(package: &str, version: &str, users: &[&str]) -> Duplicate {
+        let mut duplicate: Duplicate = serde_json::from_value(json!({
+            "package": package,
+            "version": version,
+            "source": null,
+            "kind": "major",
+            "canonical": null,
+            "latest": version,
+            "users": [],
+        }))
+        .unwrap();
+        duplicate.users = users
+            .iter()
+            .map(|user| Package {
+                name: user.parse().unwrap(),
+                version: "0.1.0".parse().unwrap(),
+                source: None,
+                checksum: None,
+                dependencies: vec![],
+                replace: None,
+            })
+            .collect();
+        duplicate
+    }
+
+    fn summary(diff: &DuplicateDiff) -> (Vec<String>, Vec<String>, Vec<Vec<String>>) {
+        let name = |duplicate: &Duplicate| format!("{} {}", duplicate.package, duplicate.version);
+        (
+            diff.added.iter().map(name).collect(),
+            diff.removed.iter().map(name).collect(),
+            diff.changed
+                .iter()
+                .map(|changed| changed.changes.clone())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn identical_sides_have_no_diff() {
+        let side = vec![duplicate("foo", "1.0.0", &["app"])];
+        assert!(diff_duplicates(side.clone(), side).is_empty());
+    }
+
+    #[test]
+    fn compares_copies_of_the_same_version() {
+        let diff = diff_duplicates(
+            vec![duplicate("foo", "1.0.0", &["app", "old"])],
+            vec![duplicate("foo", "1.0.0", &["app", "new"])],
+        );
+        assert_eq!(
+            summary(&diff),
+            (
+                vec![],
+                vec![],
+                vec![vec![
+                    "new user new 0.1.0".to_string(),
+                    "no longer used by old 0.1.0".to_string()
+                ]]
+            )
+        );
+    }
+
+    #[test]
+    fn pairs_leftover_copies_as_version_changes() {
+        // 2.0.0 is on both sides, and 1.0.0 became 1.1.0
+        let diff = diff_duplicates(
+            vec![
+                duplicate("foo", "1.0.0", &["app"]),
+                duplicate("foo", "2.0.0", &["app"]),
+            ],
+            vec![
+                duplicate("foo", "2.0.0", &["app"]),
+                duplicate("foo", "1.1.0", &["app"]),
+            ],
+        );
+        assert_eq!(
+            summary(&diff),
+            (
+                vec![],
+                vec![],
+                vec![vec!["version 1.0.0 -> 1.1.0".to_string()]]
+            )
+        );
+    }
+
+    #[test]
+    fn unpaired_copies_are_added_or_removed() {
+        let diff = diff_duplicates(
+            vec![
+                duplicate("foo", "1.0.0", &["app"]),
+                duplicate("foo", "1.1.0", &["app"]),
+                duplicate("bar", "1.0.0", &["app"]),
+            ],
+            vec![
+                duplicate("foo", "1.2.0", &["app"]),
+                duplicate("baz", "0.1.0", &["app"]),
+            ],
+        );
+        assert_eq!(
+            summary(&diff),
+            (
+                vec!["baz 0.1.0".to_string()],
+                vec!["bar 1.0.0".to_string(), "foo 1.1.0".to_string()],
+                vec![vec!["version 1.0.0 -> 1.2.0".to_string()]]
+            )
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::{Display, Formatter};
+
+/// Errors produced while analyzing a dependency graph.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading an input file failed.
+    Io(std::io::Error),
+    /// The lockfile could not be parsed.
+    Lockfile(cargo_lock::Error),
+    /// A request to the registry failed.
+    Http(reqwest::Error),
+    /// A registry response or JSON input could not be decoded.
+    Json(serde_json::Error),
+    /// A version string was not valid semver.
+    Version(semver::Error),
+    /// A package depends on something that is not in the lockfile.
+    MissingDependency { package: String, dependency: String },
+    /// The registry did not report a newest version for a crate.
+    NoLatestVersion(String),
+    /// A crate could not be found in the registry index or its local cache.
+    NotInIndex(String),
+    /// An external command exited unsuccessfully.
+    Command { program: String, stderr: String },
+    /// `cargo metadata` output did not describe a usable dependency graph.
+    Metadata(String),
+    /// A `cfg(...)` expression or `rustc --print cfg` line could not be parsed.
+    Cfg(String),
+    /// An option value was not understood.
+    InvalidArgument(String),
+    /// A package manifest could not be found or parsed.
+    Manifest(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Lockfile(e) => write!(f, "failed to parse lockfile: {e}"),
+            Error::Http(e) => write!(f, "registry request failed: {e}"),
+            Error::Json(e) => write!(f, "invalid JSON: {e}"),
+            Error::Version(e) => write!(f, "invalid version: {e}"),
+            Error::MissingDependency {
+                package,
+                dependency,
+            } => write!(f, "{package} depends on {dependency}, which was not found"),
+            Error::NoLatestVersion(package) => {
+                write!(f, "no version found for {package}")
+            }
+            Error::NotInIndex(package) => {
+                write!(f, "{package} was not found in the registry index")
+            }
+            Error::Command { program, stderr } => write!(f, "{program} failed: {stderr}"),
+            Error::Metadata(message) => write!(f, "invalid cargo metadata: {message}"),
+            Error::Cfg(message) => write!(f, "invalid cfg: {message}"),
+            Error::InvalidArgument(message) => write!(f, "{message}"),
+            Error::Manifest(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Lockfile(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Version(e) => Some(e),
+            Error::MissingDependency { .. }
+            | Error::NoLatestVersion(_)
+            | Error::NotInIndex(_)
+            | Error::Command { .. }
+            | Error::Metadata(_)
+            | Error::Cfg(_)
+            | Error::InvalidArgument(_)
+            | Error::Manifest(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<cargo_lock::Error> for Error {
+    fn from(e: cargo_lock::Error) -> Self {
+        Error::Lockfile(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<semver::Error> for Error {
+    fn from(e: semver::Error) -> Self {
+        Error::Version(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,318 @@
+//! Rewrites `Cargo.lock` so semver compatible duplicates share a single version.
+
+use crate::kind::DuplicateKind;
+use crate::manifest::read_requirements;
+use crate::Duplicate;
+use cargo_lock::{Dependency, Lockfile, MetadataKey};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One duplicate that was moved onto its canonical version.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Unification {
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    /// The packages whose `dependencies` now point at `to`, as `name version`.
+    pub users: Vec<String>,
+}
+
+/// A compatible duplicate that was left alone, and why.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Refusal {
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+/// What [`fix_lockfile`] changed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FixReport {
+    pub unified: Vec<Unification>,
+    /// `[[package]]` entries dropped because nothing depends on them any more, as
+    /// `name version`.
+    pub removed: Vec<String>,
+    pub refused: Vec<Refusal>,
+}
+
+impl FixReport {
+    pub fn is_empty(&self) -> bool {
+        self.unified.is_empty()
+    }
+}
+
+/// Moves every semver compatible duplicate in `lockfile` onto its canonical version.
+///
+/// A duplicate is only moved when the canonical copy is in the lockfile with the
+/// same source, and the manifest of every user has a requirement that admits it.
+/// Members and other path packages are looked up under `workspace_root`. Packages
+/// left without users are removed afterwards.
+pub fn fix_lockfile(
+    lockfile: &mut Lockfile,
+    duplicates: &[Duplicate],
+    workspace_root: &Path,
+) -> FixReport {
+    let mut report = FixReport::default();
+    let mut orphans = vec![];
+    for duplicate in duplicates {
+        if duplicate.kind != DuplicateKind::Compatible {
+            continue;
+        }
+        let Some(canonical) = &duplicate.canonical else {
+            continue;
+        };
+        let refuse = |reason: String| Refusal {
+            package: duplicate.package.clone(),
+            from: duplicate.version.clone(),
+            to: canonical.clone(),
+            reason,
+        };
+        let (Ok(version), Ok(to)) = (
+            Version::parse(&duplicate.version),
+            Version::parse(canonical),
+        ) else {
+            continue;
+        };
+        let Some(target) = lockfile.packages.iter().find(|package| {
+            package.name.as_str() == duplicate.package
+                && package.version == to
+                && package.source == duplicate.source
+        }) else {
+            report.refused.push(refuse(format!(
+                "{} {canonical} from the same source is not in Cargo.lock",
+                duplicate.package
+            )));
+            continue;
+        };
+        let target = Dependency::from(target);
+        let from = Dependency {
+            name: target.name.clone(),
+            version,
+            source: duplicate.source.clone(),
+        };
+
+        let users: Vec<usize> = (0..lockfile.packages.len())
+            .filter(|i| lockfile.packages[*i].dependencies.contains(&from))
+            .collect();
+        let mut refusal = None;
+        for user in users.iter().map(|i| &lockfile.packages[*i]) {
+            let reason = match read_requirements(user, &duplicate.package, workspace_root) {
+                Ok(reqs) if reqs.is_empty() => format!(
+                    "{} {} does not declare a dependency on {}",
+                    user.name, user.version, duplicate.package
+                ),
+                Ok(reqs) => match reqs.iter().find(|req| !req.matches(&to)) {
+                    Some(req) => format!(
+                        "{} {} requires {} {req}",
+                        user.name, user.version, duplicate.package
+                    ),
+                    None => continue,
+                },
+                Err(e) => e.to_string(),
+            };
+            refusal = Some(reason);
+            break;
+        }
+        if let Some(reason) = refusal {
+            report.refused.push(refuse(reason));
+            continue;
+        }
+
+        let mut unification = Unification {
+            package: duplicate.package.clone(),
+            from: duplicate.version.clone(),
+            to: canonical.clone(),
+            users: vec![],
+        };
+        for i in users {
+            let user = &mut lockfile.packages[i];
+            user.dependencies.retain(|dep| *dep != from);
+            if !user.dependencies.contains(&target) {
+                user.dependencies.push(target.clone());
+                user.dependencies.sort();
+            }
+            unification
+                .users
+                .push(format!("{} {}", user.name, user.version));
+        }
+        report.unified.push(unification);
+        orphans.push(from);
+    }
+
+    // Drop the replaced packages, then whatever only they depended on
+    while let Some(id) = orphans.pop() {
+        if lockfile
+            .packages
+            .iter()
+            .any(|package| package.dependencies.contains(&id))
+        {
+            continue;
+        }
+        let Some(position) = lockfile
+            .packages
+            .iter()
+            .position(|package| package.source.is_some() && Dependency::from(package) == id)
+        else {
+            continue;
+        };
+        let package = lockfile.packages.remove(position);
+        lockfile.metadata.remove(&MetadataKey::for_checksum(&id));
+        report
+            .removed
+            .push(format!("{} {}", package.name, package.version));
+        orphans.extend(package.dependencies);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{build_package_map, Graph};
+    use crate::{find_duplicates, Options};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    const LOCKFILE: &str = r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "foo 1.0.0",
+ "lib",
+]
+
+[[package]]
+name = "bar"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "baz",
+]
+
+[[package]]
+name = "baz"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "foo"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "bar",
+]
+
+[[package]]
+name = "foo"
+version = "1.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "lib"
+version = "0.1.0"
+dependencies = [
+ "foo 1.2.0",
+]
+"#;
+
+    /// Writes a workspace whose `app` member depends on `foo` with `app_dep`, and
+    /// whose `[workspace.dependencies]` pins `foo = "=1.0.0"`.
+    fn workspace(test: &str, app_dep: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "cargo-duplicated-deps-{test}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\", \"lib\"]\n\n[workspace.dependencies]\nfoo = \"=1.0.0\"\n",
+        )
+        .unwrap();
+        for (name, dep) in [("app", app_dep), ("lib", "\"1.2\"")] {
+            let dir = root.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = {dep}\n"
+                ),
+            )
+            .unwrap();
+        }
+        root
+    }
+
+    async fn fix(test: &str, app_dep: &str) -> (Lockfile, FixReport) {
+        let mut lockfile = Lockfile::from_str(LOCKFILE).unwrap();
+        let package_map = build_package_map(&Graph::from(&lockfile)).unwrap();
+        let options = Options {
+            offline: true,
+            ..Options::default()
+        };
+        let duplicates = find_duplicates(&package_map, &options).await.unwrap();
+        let root = workspace(test, app_dep);
+        let report = fix_lockfile(&mut lockfile, &duplicates, &root);
+        std::fs::remove_dir_all(root).unwrap();
+        (lockfile, report)
+    }
+
+    fn names(lockfile: &Lockfile) -> Vec<String> {
+        lockfile
+            .packages
+            .iter()
+            .map(|package| format!("{} {}", package.name, package.version))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn unifies_a_compatible_duplicate() {
+        let (lockfile, report) = fix("unify", "\"1\"").await;
+        assert_eq!(report.unified.len(), 1);
+        let unification = &report.unified[0];
+        assert_eq!(
+            (unification.from.as_str(), unification.to.as_str()),
+            ("1.0.0", "1.2.0")
+        );
+        assert_eq!(unification.users, ["app 0.1.0"]);
+        let app = &lockfile.packages[0];
+        let foo: Vec<String> = app
+            .dependencies
+            .iter()
+            .filter(|dep| dep.name.as_str() == "foo")
+            .map(|dep| dep.version.to_string())
+            .collect();
+        assert_eq!(foo, ["1.2.0"]);
+    }
+
+    #[tokio::test]
+    async fn removes_the_orphaned_chain() {
+        let (lockfile, report) = fix("orphans", "\"1\"").await;
+        assert_eq!(report.removed, ["foo 1.0.0", "bar 0.1.0", "baz 0.1.0"]);
+        assert_eq!(names(&lockfile), ["app 0.1.0", "foo 1.2.0", "lib 0.1.0"]);
+    }
+
+    #[tokio::test]
+    async fn refuses_when_a_requirement_excludes_the_canonical_version() {
+        let (lockfile, report) = fix("refuse", "\"=1.0.0\"").await;
+        assert!(report.is_empty());
+        assert!(report.removed.is_empty());
+        assert_eq!(report.refused.len(), 1);
+        assert_eq!(report.refused[0].reason, "app 0.1.0 requires foo =1.0.0");
+        assert_eq!(
+            lockfile.to_string(),
+            Lockfile::from_str(LOCKFILE).unwrap().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_when_an_inherited_requirement_excludes_the_canonical_version() {
+        let (_, report) = fix("inherit", "{ workspace = true }").await;
+        assert!(report.is_empty());
+        assert_eq!(report.refused.len(), 1);
+        assert_eq!(report.refused[0].reason, "app 0.1.0 requires foo =1.0.0");
+    }
+}
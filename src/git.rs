@@ -0,0 +1,75 @@
+//! Reads files as they were at earlier commits, through the local `git` command.
+
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs `git` in `dir` and returns its standard output.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let git = std::env::var_os("GIT").unwrap_or_else(|| "git".into());
+    let output = Command::new(git).arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::Command {
+            program: format!("git {}", args.join(" ")),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Splits `path` into the directory git runs in and the file name within it.
+pub(crate) fn split_path(path: &Path) -> Result<(&Path, String)> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidArgument(format!("{} is not a file", path.display())))?;
+    Ok((dir, name.to_string_lossy().into_owned()))
+}
+
+/// The root of the working tree that the file at `path` is in, like
+/// `git rev-parse --show-toplevel`.
+pub fn toplevel(path: &Path) -> Result<PathBuf> {
+    let (dir, _) = split_path(path)?;
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(root.trim_end_matches(['\n', '\r'])))
+}
+
+/// The contents of the file at `path` in the commit `rev`, like `git show rev:path`.
+pub fn show_file(rev: &str, path: &Path) -> Result<String> {
+    let (dir, name) = split_path(path)?;
+    git(dir, &["show", &format!("{rev}:./{name}")])
+}
+
+/// A commit, as listed by [`log`].
+#[derive(Clone, Debug)]
+pub struct Revision {
+    pub commit: String,
+    /// The committer date in strict ISO 8601 format.
+    pub date: String,
+}
+
+/// The commits that changed the file at `path`, oldest first.
+pub fn log(path: &Path) -> Result<Vec<Revision>> {
+    let (dir, name) = split_path(path)?;
+    let output = git(
+        dir,
+        &[
+            "log",
+            "--reverse",
+            "--format=%H %cI",
+            "--",
+            &format!("./{name}"),
+        ],
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(commit, date)| Revision {
+            commit: commit.to_string(),
+            date: date.to_string(),
+        })
+        .collect())
+}
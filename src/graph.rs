@@ -0,0 +1,460 @@
+use crate::error::{Error, Result};
+use cargo_lock::{Dependency, Lockfile, Package};
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The kind of dependency edge, as in `[dependencies]`, `[build-dependencies]` and
+/// `[dev-dependencies]`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DepKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl DepKind {
+    pub const ALL: [DepKind; 3] = [DepKind::Normal, DepKind::Build, DepKind::Dev];
+}
+
+impl Display for DepKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DepKind::Normal => write!(f, "normal"),
+            DepKind::Build => write!(f, "build"),
+            DepKind::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl FromStr for DepKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "normal" => Ok(DepKind::Normal),
+            "build" => Ok(DepKind::Build),
+            "dev" => Ok(DepKind::Dev),
+            _ => Err(Error::InvalidArgument(format!("unknown edge kind `{s}`"))),
+        }
+    }
+}
+
+/// Parses a `cargo tree --edges` style list, such as `normal,build` or `no-dev`.
+pub fn parse_edge_kinds<S: AsRef<str>>(specs: &[S]) -> Result<BTreeSet<DepKind>> {
+    let mut included = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    for spec in specs.iter().flat_map(|spec| spec.as_ref().split(',')) {
+        match spec.trim() {
+            "all" => included.extend(DepKind::ALL),
+            "no-normal" => excluded.extend([DepKind::Normal]),
+            "no-build" => excluded.extend([DepKind::Build]),
+            "no-dev" => excluded.extend([DepKind::Dev]),
+            kind => included.extend([kind.parse::<DepKind>()?]),
+        }
+    }
+    if included.is_empty() {
+        included.extend(DepKind::ALL);
+    }
+    Ok(&included - &excluded)
+}
+
+/// The packages of a dependency graph, independent of the backend that produced it.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    pub packages: Vec<Package>,
+    /// Kinds of each `(user, dependency)` edge. Lockfiles do not record edge
+    /// kinds, so this is empty for graphs read from `Cargo.lock`.
+    pub edge_kinds: HashMap<(Dependency, Dependency), BTreeSet<DepKind>>,
+    /// The `(crate name, requirement)` pairs each package declares in its manifest,
+    /// for backends that know them. Lockfiles do not, so this is empty for graphs
+    /// read from `Cargo.lock`.
+    pub requirements: HashMap<Dependency, Vec<(String, VersionReq)>>,
+}
+
+impl From<&Lockfile> for Graph {
+    fn from(lockfile: &Lockfile) -> Self {
+        Graph {
+            packages: lockfile.packages.clone(),
+            edge_kinds: HashMap::new(),
+            requirements: HashMap::new(),
+        }
+    }
+}
+
+/// A single package, together with every package that depends on it.
+#[derive(Clone, Debug)]
+pub struct PackageInfo {
+    /// Full identity of the package: name, version and source. Two packages with
+    /// the same name and version but different sources are compiled separately.
+    pub id: Dependency,
+    pub users: Vec<Package>,
+    /// Kinds of the edges from `users`, when the backend knows them.
+    pub kinds: BTreeSet<DepKind>,
+}
+
+/// Every package in the graph, grouped by package name.
+pub type PackageMap = HashMap<String, Vec<PackageInfo>>;
+
+pub fn build_package_map(graph: &Graph) -> Result<PackageMap> {
+    let packages = &graph.packages;
+    let mut package_map: PackageMap = HashMap::new();
+
+    // Pass 1: insert packages
+    for package in packages {
+        let info = PackageInfo {
+            id: Dependency::from(package),
+            users: vec![],
+            kinds: BTreeSet::new(),
+        };
+        if let Some(s) = package_map.get_mut(package.name.as_str()) {
+            s.push(info);
+        } else {
+            package_map.insert(package.name.to_string(), vec![info]);
+        }
+    }
+
+    // Pass 2: insert users
+    for package in packages {
+        for dep in &package.dependencies {
+            let info = package_map
+                .get_mut(dep.name.as_str())
+                .and_then(|s| s.iter_mut().find(|info| info.id == *dep))
+                .ok_or_else(|| Error::MissingDependency {
+                    package: package.name.to_string(),
+                    dependency: dep.to_string(),
+                })?;
+            info.users.push(package.clone());
+            if let Some(kinds) = graph
+                .edge_kinds
+                .get(&(Dependency::from(package), dep.clone()))
+            {
+                info.kinds.extend(kinds);
+            }
+        }
+    }
+
+    Ok(package_map)
+}
+
+/// Looks up a package by its full identity.
+pub fn find_info<'a>(package_map: &'a PackageMap, id: &Dependency) -> Option<&'a PackageInfo> {
+    package_map
+        .get(id.name.as_str())?
+        .iter()
+        .find(|info| info.id == *id)
+}
+
+/// Finds the shortest chain of users from `package` to a workspace member, which is
+/// a package without a `source`. With `root`, the chain must end at the member of that
+/// name. Returns `None` when no such member depends on `package`.
+pub fn get_usage_chain(
+    package_map: &PackageMap,
+    package: &Package,
+    root: Option<&str>,
+) -> Option<String> {
+    let is_goal = |package: &Package| {
+        package.source.is_none() && root.is_none_or(|root| package.name.as_str() == root)
+    };
+
+    // Breadth-first search over the reverse dependency graph, remembering how each
+    // package was reached so the chain can be rebuilt once a member is found
+    let mut parents: BTreeMap<&Package, Option<&Package>> = BTreeMap::from([(package, None)]);
+    let mut queue = VecDeque::from([package]);
+    while let Some(current) = queue.pop_front() {
+        if is_goal(current) {
+            let mut chain = vec![];
+            let mut next = Some(current);
+            while let Some(package) = next {
+                chain.push(format!("{} v{}", package.name, package.version));
+                next = parents[package];
+            }
+            chain.reverse();
+            return Some(chain.join(" -> "));
+        }
+        let Some(info) = find_info(package_map, &Dependency::from(current)) else {
+            continue;
+        };
+        for user in &info.users {
+            if !parents.contains_key(user) {
+                parents.insert(user, Some(current));
+                queue.push_back(user);
+            }
+        }
+    }
+    None
+}
+
+/// The fewest dependency edges between a workspace member and the package `id`, or
+/// `None` when no member depends on it.
+pub fn get_depth(package_map: &PackageMap, id: &Dependency) -> Option<usize> {
+    let mut seen = HashSet::from([id.clone()]);
+    let mut queue = VecDeque::from([(id.clone(), 0)]);
+    while let Some((current, depth)) = queue.pop_front() {
+        if current.source.is_none() {
+            return Some(depth);
+        }
+        let Some(info) = find_info(package_map, &current) else {
+            continue;
+        };
+        for user in &info.users {
+            let user = Dependency::from(user);
+            if seen.insert(user.clone()) {
+                queue.push_back((user, depth + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Bounds on the search done by [`get_usage_paths`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathLimits {
+    /// Stop after this many paths.
+    pub max_paths: Option<usize>,
+    /// Ignore paths with more than this many packages.
+    pub max_depth: Option<usize>,
+}
+
+/// Lists every distinct path by which the package `id` is pulled in. Each path
+/// starts at a direct user and ends at a workspace member, or at a package that
+/// nothing depends on. With `root`, paths must end at the member of that name, as
+/// in [`get_usage_chain`].
+pub fn get_usage_paths(
+    package_map: &PackageMap,
+    id: &Dependency,
+    limits: PathLimits,
+    root: Option<&str>,
+) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        package_map: &'a PackageMap,
+        info: &'a PackageInfo,
+        path: &mut Vec<&'a Package>,
+        paths: &mut Vec<Vec<String>>,
+        limits: PathLimits,
+        root: Option<&str>,
+    ) {
+        for user in &info.users {
+            if limits.max_paths.is_some_and(|max| paths.len() >= max) {
+                return;
+            }
+            if limits.max_depth.is_some_and(|max| path.len() >= max) {
+                return;
+            }
+            // Dev-dependencies can form cycles
+            if path.contains(&user) {
+                continue;
+            }
+            path.push(user);
+            let is_end =
+                user.source.is_none() && root.is_none_or(|root| user.name.as_str() == root);
+            let next = find_info(package_map, &Dependency::from(user));
+            match next {
+                Some(next) if !is_end && !next.users.is_empty() => {
+                    visit(package_map, next, path, paths, limits, root);
+                }
+                // Paths that run out before reaching `root` are not usage paths of it
+                _ if !is_end && root.is_some() => {}
+                _ => paths.push(
+                    path.iter()
+                        .map(|package| format!("{} v{}", package.name, package.version))
+                        .collect(),
+                ),
+            }
+            path.pop();
+        }
+    }
+
+    let mut paths = vec![];
+    if let Some(info) = find_info(package_map, id) {
+        visit(package_map, info, &mut vec![], &mut paths, limits, root);
+    }
+    paths
+}
+
+/// Lists the packages that are only in the graph because of the package `id`: its
+/// direct and indirect dependencies that no workspace member reaches without going
+/// through it. Sorted, as `name version`.
+pub fn get_exclusive_subtree(package_map: &PackageMap, id: &Dependency) -> Vec<String> {
+    fn reach<'a>(
+        dependencies: &HashMap<Dependency, Vec<&'a Dependency>>,
+        start: Vec<&'a Dependency>,
+        skip: Option<&Dependency>,
+    ) -> HashSet<&'a Dependency> {
+        let mut seen: HashSet<&Dependency> = HashSet::new();
+        let mut queue: VecDeque<&Dependency> = start.into_iter().collect();
+        while let Some(current) = queue.pop_front() {
+            if Some(current) == skip || !seen.insert(current) {
+                continue;
+            }
+            if let Some(next) = dependencies.get(current) {
+                queue.extend(next);
+            }
+        }
+        seen
+    }
+
+    // The package map only records users, so invert it into dependency lists
+    let mut dependencies: HashMap<Dependency, Vec<&Dependency>> = HashMap::new();
+    let mut roots = vec![];
+    for info in package_map.values().flatten() {
+        if info.users.is_empty() || info.id.source.is_none() {
+            roots.push(&info.id);
+        }
+        for user in &info.users {
+            dependencies
+                .entry(Dependency::from(user))
+                .or_default()
+                .push(&info.id);
+        }
+    }
+    let Some(info) = find_info(package_map, id) else {
+        return vec![];
+    };
+    let shared = reach(&dependencies, roots, Some(id));
+    let mut exclusive: Vec<&Dependency> = reach(&dependencies, vec![&info.id], None)
+        .into_iter()
+        .filter(|package| *package != id && !shared.contains(package))
+        .collect();
+    exclusive.sort();
+    exclusive
+        .into_iter()
+        .map(|package| format!("{} v{}", package.name, package.version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A lockfile of `(name, dependencies)` packages at version 1.0.0, where the
+    /// `app` packages are workspace members and everything else comes from crates.io.
+    fn package_map(packages: &[(&str, &[&str])]) -> PackageMap {
+        let mut text = String::from("version = 3\n");
+        for (name, dependencies) in packages {
+            text.push_str(&format!(
+                "\n[[package]]\nname = \"{name}\"\nversion = \"1.0.0\"\n"
+            ));
+            if !name.starts_with("app") {
+                text.push_str(
+                    "source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                );
+            }
+            let dependencies: Vec<String> = dependencies
+                .iter()
+                .map(|dep| format!("\"{dep}\""))
+                .collect();
+            text.push_str(&format!("dependencies = [{}]\n", dependencies.join(", ")));
+        }
+        let lockfile: Lockfile = text.parse().unwrap();
+        build_package_map(&Graph::from(&lockfile)).unwrap()
+    }
+
+    fn id<'a>(package_map: &'a PackageMap, name: &str) -> &'a Dependency {
+        &package_map[name][0].id
+    }
+
+    #[test]
+    fn exclusive_subtree_includes_diamonds_below_the_package() {
+        // foo -> left -> base and foo -> right -> base
+        let package_map = package_map(&[
+            ("app", &["foo"]),
+            ("foo", &["left", "right"]),
+            ("left", &["base"]),
+            ("right", &["base"]),
+            ("base", &[]),
+        ]);
+        assert_eq!(
+            get_exclusive_subtree(&package_map, id(&package_map, "foo")),
+            ["base v1.0.0", "left v1.0.0", "right v1.0.0"]
+        );
+    }
+
+    #[test]
+    fn exclusive_subtree_excludes_diamonds_shared_with_other_users() {
+        // foo -> left -> base, and bar -> right -> base
+        let package_map = package_map(&[
+            ("app", &["foo", "bar"]),
+            ("foo", &["left", "shared"]),
+            ("bar", &["right", "shared"]),
+            ("left", &["base"]),
+            ("right", &["base"]),
+            ("base", &[]),
+            ("shared", &[]),
+        ]);
+        assert_eq!(
+            get_exclusive_subtree(&package_map, id(&package_map, "foo")),
+            ["left v1.0.0"]
+        );
+        assert_eq!(
+            get_exclusive_subtree(&package_map, id(&package_map, "bar")),
+            ["right v1.0.0"]
+        );
+    }
+
+    #[test]
+    fn exclusive_subtree_of_a_leaf_is_empty() {
+        let package_map = package_map(&[("app", &["foo"]), ("foo", &[])]);
+        assert!(get_exclusive_subtree(&package_map, id(&package_map, "foo")).is_empty());
+    }
+
+    fn paths(package_map: &PackageMap, limits: PathLimits, root: Option<&str>) -> Vec<String> {
+        let mut paths: Vec<String> =
+            get_usage_paths(package_map, id(package_map, "foo"), limits, root)
+                .iter()
+                .map(|path| path.join(" -> "))
+                .collect();
+        paths.sort();
+        paths
+    }
+
+    /// `foo` is used by the member `app` and by `lib`, which both members use.
+    fn two_members() -> PackageMap {
+        package_map(&[
+            ("app", &["foo", "lib"]),
+            ("app-cli", &["app", "lib"]),
+            ("lib", &["foo"]),
+            ("foo", &[]),
+        ])
+    }
+
+    #[test]
+    fn usage_paths_end_at_the_chain_root() {
+        let package_map = two_members();
+        assert_eq!(
+            paths(&package_map, PathLimits::default(), None),
+            [
+                "app v1.0.0",
+                "lib v1.0.0 -> app v1.0.0",
+                "lib v1.0.0 -> app-cli v1.0.0"
+            ]
+        );
+        assert_eq!(
+            paths(&package_map, PathLimits::default(), Some("app-cli")),
+            [
+                "app v1.0.0 -> app-cli v1.0.0",
+                "lib v1.0.0 -> app v1.0.0 -> app-cli v1.0.0",
+                "lib v1.0.0 -> app-cli v1.0.0"
+            ]
+        );
+        assert!(paths(&package_map, PathLimits::default(), Some("other")).is_empty());
+    }
+
+    #[test]
+    fn usage_paths_stay_within_limits() {
+        let package_map = two_members();
+        let limits = PathLimits {
+            max_paths: Some(2),
+            max_depth: None,
+        };
+        assert_eq!(paths(&package_map, limits, None).len(), 2);
+        let limits = PathLimits {
+            max_paths: None,
+            max_depth: Some(1),
+        };
+        assert_eq!(paths(&package_map, limits, None), ["app v1.0.0"]);
+    }
+}
@@ -0,0 +1,48 @@
+//! A time series of the duplicates in a lockfile across its git history.
+
+use crate::git::Revision;
+use crate::Duplicate;
+use serde::{Deserialize, Serialize};
+
+/// The duplicates found at one commit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryPoint {
+    pub commit: String,
+    pub date: String,
+    pub duplicates: usize,
+    /// The sum of the exclusive weights of the duplicates.
+    pub weight: usize,
+    /// Every duplicated copy, as `name@version`.
+    pub crates: Vec<String>,
+}
+
+impl HistoryPoint {
+    pub fn new(revision: &Revision, duplicates: &[Duplicate]) -> Self {
+        HistoryPoint {
+            commit: revision.commit.clone(),
+            date: revision.date.clone(),
+            duplicates: duplicates.len(),
+            weight: duplicates.iter().map(|duplicate| duplicate.weight).sum(),
+            crates: duplicates
+                .iter()
+                .map(|duplicate| format!("{}@{}", duplicate.package, duplicate.version))
+                .collect(),
+        }
+    }
+}
+
+/// Renders the points as CSV, with the crates of each point separated by spaces.
+pub fn to_csv(points: &[HistoryPoint]) -> String {
+    let mut csv = String::from("commit,date,duplicates,weight,crates\n");
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            point.commit,
+            point.date,
+            point.duplicates,
+            point.weight,
+            point.crates.join(" ")
+        ));
+    }
+    csv
+}
@@ -0,0 +1,160 @@
+//! JUnit XML output, for CI systems that show test results natively.
+
+use crate::config::Suppressed;
+use crate::graph::{get_usage_chain, PackageMap};
+use crate::Duplicate;
+use cargo_lock::SourceId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write};
+
+const SUITE: &str = "duplicated-deps";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A version, followed by its source when that is not crates.io, so copies of the
+/// same version from different sources stay apart.
+fn version(version: &impl Display, source: Option<&SourceId>) -> String {
+    match source {
+        Some(source) if !source.is_default_registry() => format!("{version} ({source})"),
+        Some(_) => version.to_string(),
+        None => format!("{version} (path)"),
+    }
+}
+
+enum Case<'a> {
+    Failure(Vec<&'a Duplicate>),
+    Skipped(Vec<&'a Suppressed>),
+}
+
+/// Renders a report as one test case per duplicated crate. A crate fails when any of
+/// its duplicates is reported, with every version of the crate and the usage chains
+/// of the duplicates in the failure, and is skipped when the ignore list suppresses
+/// all of them.
+pub fn to_junit(
+    duplicates: &[Duplicate],
+    suppressed: &[Suppressed],
+    package_map: &PackageMap,
+    chain_root: Option<&str>,
+) -> String {
+    let mut cases: BTreeMap<&str, Case> = BTreeMap::new();
+    for duplicate in duplicates {
+        if let Case::Failure(failures) = cases
+            .entry(&duplicate.package)
+            .or_insert_with(|| Case::Failure(vec![]))
+        {
+            failures.push(duplicate);
+        }
+    }
+    for suppressed in suppressed {
+        if let Case::Skipped(skipped) = cases
+            .entry(&suppressed.package)
+            .or_insert_with(|| Case::Skipped(vec![]))
+        {
+            skipped.push(suppressed);
+        }
+    }
+    let failures = cases
+        .values()
+        .filter(|case| matches!(case, Case::Failure(_)))
+        .count();
+    let skipped = cases.len() - failures;
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    _ = writeln!(
+        out,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\">",
+        env!("CARGO_PKG_NAME"),
+        cases.len()
+    );
+    _ = writeln!(
+        out,
+        "  <testsuite name=\"{SUITE}\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\">",
+        cases.len()
+    );
+    for (name, case) in &cases {
+        _ = writeln!(
+            out,
+            "    <testcase classname=\"{SUITE}\" name=\"{}\">",
+            escape(name)
+        );
+        match case {
+            Case::Failure(failures) => {
+                let versions: BTreeSet<String> = package_map
+                    .get(*name)
+                    .into_iter()
+                    .flatten()
+                    .map(|info| version(&info.id.version, info.id.source.as_ref()))
+                    .collect();
+                let versions: Vec<String> = versions.into_iter().collect();
+                let kinds: BTreeSet<String> = failures
+                    .iter()
+                    .map(|duplicate| duplicate.kind.to_string())
+                    .collect();
+                let kinds: Vec<String> = kinds.into_iter().collect();
+                let message = format!("{name} is duplicated: versions {}", versions.join(", "));
+                let mut text = String::new();
+                for duplicate in failures {
+                    _ = write!(
+                        text,
+                        "{name} v{} is a {} duplicate",
+                        version(&duplicate.version, duplicate.source.as_ref()),
+                        duplicate.kind
+                    );
+                    if let Some(canonical) = &duplicate.canonical {
+                        _ = write!(text, " of v{canonical}");
+                    }
+                    text.push_str(", used by:\n");
+                    for user in &duplicate.users {
+                        match get_usage_chain(package_map, user, chain_root) {
+                            Some(chain) => _ = writeln!(text, "  {chain}"),
+                            None => _ = writeln!(text, "  {} v{}", user.name, user.version),
+                        }
+                    }
+                }
+                _ = writeln!(
+                    out,
+                    "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+                    escape(&message),
+                    escape(&kinds.join(", ")),
+                    escape(&text)
+                );
+            }
+            Case::Skipped(skipped) => {
+                let reasons: Vec<String> = skipped
+                    .iter()
+                    .map(|suppressed| match &suppressed.reason {
+                        Some(reason) => format!(
+                            "v{} ignored by {}: {reason}",
+                            suppressed.version, suppressed.ignored_by
+                        ),
+                        None => format!(
+                            "v{} ignored by {}",
+                            suppressed.version, suppressed.ignored_by
+                        ),
+                    })
+                    .collect();
+                _ = writeln!(
+                    out,
+                    "      <skipped message=\"{}\"/>",
+                    escape(&reasons.join("; "))
+                );
+            }
+        }
+        out.push_str("    </testcase>\n");
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
@@ -0,0 +1,148 @@
+//! Classification of duplicates by how hard they are to unify.
+
+use crate::error::{Error, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Why a package counts as a duplicate, judged against the canonical copy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateKind {
+    /// Semver compatible with the canonical version, so `cargo update` can usually
+    /// unify the two.
+    Compatible,
+    /// A different major version.
+    Major,
+    /// A different minor version of a `0.x` crate, which semver treats as breaking.
+    ZeroMinor,
+    /// One of the two versions is a prerelease.
+    Prerelease,
+    /// The same version of a crate from a different source, such as a git fork.
+    DifferentSource,
+}
+
+impl DuplicateKind {
+    pub const ALL: [DuplicateKind; 5] = [
+        DuplicateKind::Compatible,
+        DuplicateKind::Major,
+        DuplicateKind::ZeroMinor,
+        DuplicateKind::Prerelease,
+        DuplicateKind::DifferentSource,
+    ];
+
+    /// Classifies `version` against the `canonical` version of the same crate. Copies
+    /// with equal versions are only duplicated because of their source.
+    pub fn classify(version: &Version, canonical: &Version) -> Self {
+        if version == canonical {
+            DuplicateKind::DifferentSource
+        } else if !version.pre.is_empty() || !canonical.pre.is_empty() {
+            DuplicateKind::Prerelease
+        } else if version.major != canonical.major {
+            DuplicateKind::Major
+        } else if version.major == 0 && (version.minor != canonical.minor || version.minor == 0) {
+            DuplicateKind::ZeroMinor
+        } else {
+            DuplicateKind::Compatible
+        }
+    }
+}
+
+impl Display for DuplicateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicateKind::Compatible => write!(f, "compatible"),
+            DuplicateKind::Major => write!(f, "major"),
+            DuplicateKind::ZeroMinor => write!(f, "zero-minor"),
+            DuplicateKind::Prerelease => write!(f, "prerelease"),
+            DuplicateKind::DifferentSource => write!(f, "different-source"),
+        }
+    }
+}
+
+impl FromStr for DuplicateKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "compatible" => Ok(DuplicateKind::Compatible),
+            "major" => Ok(DuplicateKind::Major),
+            "zero-minor" => Ok(DuplicateKind::ZeroMinor),
+            "prerelease" => Ok(DuplicateKind::Prerelease),
+            "different-source" => Ok(DuplicateKind::DifferentSource),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown duplicate kind `{s}`"
+            ))),
+        }
+    }
+}
+
+/// Parses a comma separated list of duplicate kinds. `incompatible` stands for every
+/// kind except `compatible`.
+pub fn parse_duplicate_kinds<S: AsRef<str>>(specs: &[S]) -> Result<BTreeSet<DuplicateKind>> {
+    let mut kinds = BTreeSet::new();
+    for spec in specs.iter().flat_map(|spec| spec.as_ref().split(',')) {
+        match spec.trim() {
+            "incompatible" => kinds.extend(
+                DuplicateKind::ALL
+                    .into_iter()
+                    .filter(|kind| *kind != DuplicateKind::Compatible),
+            ),
+            kind => kinds.extend([kind.parse::<DuplicateKind>()?]),
+        }
+    }
+    Ok(kinds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(version: &str, canonical: &str) -> DuplicateKind {
+        DuplicateKind::classify(&version.parse().unwrap(), &canonical.parse().unwrap())
+    }
+
+    #[test]
+    fn classifies_semver_compatibility() {
+        assert_eq!(classify("1.2.0", "1.4.1"), DuplicateKind::Compatible);
+        assert_eq!(classify("0.3.1", "0.3.7"), DuplicateKind::Compatible);
+        assert_eq!(classify("1.9.0", "2.0.0"), DuplicateKind::Major);
+        assert_eq!(classify("0.9.0", "1.0.0"), DuplicateKind::Major);
+        assert_eq!(classify("0.2.0", "0.3.0"), DuplicateKind::ZeroMinor);
+    }
+
+    #[test]
+    fn patch_releases_of_0_0_are_incompatible() {
+        assert_eq!(classify("0.0.1", "0.0.2"), DuplicateKind::ZeroMinor);
+        assert_eq!(classify("0.0.3", "0.1.0"), DuplicateKind::ZeroMinor);
+    }
+
+    #[test]
+    fn prereleases_take_precedence() {
+        assert_eq!(classify("1.0.0-rc.1", "1.0.0"), DuplicateKind::Prerelease);
+        assert_eq!(classify("1.2.0", "2.0.0-alpha"), DuplicateKind::Prerelease);
+        assert_eq!(
+            classify("2.0.0-alpha", "2.0.0-beta"),
+            DuplicateKind::Prerelease
+        );
+    }
+
+    #[test]
+    fn equal_versions_differ_by_source() {
+        assert_eq!(classify("1.0.0", "1.0.0"), DuplicateKind::DifferentSource);
+        assert_eq!(
+            classify("1.0.0-rc.1", "1.0.0-rc.1"),
+            DuplicateKind::DifferentSource
+        );
+    }
+
+    #[test]
+    fn parses_incompatible_as_every_kind_but_compatible() {
+        let kinds = parse_duplicate_kinds(&["incompatible"]).unwrap();
+        assert!(!kinds.contains(&DuplicateKind::Compatible));
+        assert_eq!(kinds.len(), DuplicateKind::ALL.len() - 1);
+        assert!(parse_duplicate_kinds(&["major,unknown"]).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+pub mod baseline;
+pub mod cfg;
+pub mod config;
+pub mod diagram;
+pub mod diff;
+pub mod error;
+pub mod fix;
+pub mod git;
+pub mod graph;
+pub mod history;
+pub mod junit;
+pub mod kind;
+pub mod manifest;
+pub mod markdown;
+pub mod metadata;
+pub mod plan;
+pub mod policy;
+pub mod registry;
+pub mod sarif;
+pub mod upgrade;
+
+pub use baseline::{Baseline, BaselineEntry};
+pub use config::{Config, IgnoreEntry, Suppressed};
+pub use diagram::Diagram;
+pub use diff::{diff_duplicates, Changed, DuplicateDiff};
+pub use error::{Error, Result};
+pub use fix::{fix_lockfile, FixReport};
+pub use graph::{
+    build_package_map, find_info, get_depth, get_exclusive_subtree, get_usage_chain,
+    get_usage_paths, parse_edge_kinds, DepKind, Graph, PackageInfo, PackageMap, PathLimits,
+};
+pub use history::HistoryPoint;
+pub use kind::{parse_duplicate_kinds, DuplicateKind};
+pub use metadata::{Features, Filter, Metadata};
+pub use plan::{build_plan, Plan};
+pub use policy::{Policy, PolicyReport, Violation};
+pub use registry::{get_latest_version, new_client, Index};
+pub use upgrade::{check_user, Fix, Verdict};
+
+use cargo_lock::{Lockfile, Package, SourceId};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Which copy of a duplicated crate is considered the one to keep.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Canonical {
+    /// The highest version in the graph.
+    #[default]
+    Highest,
+    /// The version with the most users.
+    MostUsed,
+    /// The newest version published to crates.io, if it is in the graph.
+    LatestRegistry,
+    /// No copy is canonical, so every copy is reported.
+    None,
+}
+
+impl Display for Canonical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Canonical::Highest => write!(f, "highest"),
+            Canonical::MostUsed => write!(f, "most-used"),
+            Canonical::LatestRegistry => write!(f, "latest-registry"),
+            Canonical::None => write!(f, "none"),
+        }
+    }
+}
+
+impl FromStr for Canonical {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "highest" => Ok(Canonical::Highest),
+            "most-used" => Ok(Canonical::MostUsed),
+            "latest-registry" => Ok(Canonical::LatestRegistry),
+            "none" => Ok(Canonical::None),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown canonical version policy `{s}`"
+            ))),
+        }
+    }
+}
+
+/// How to order reported duplicates.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Sort {
+    /// Alphabetically by crate name.
+    #[default]
+    Name,
+    /// Most users first.
+    Users,
+    /// Most packages pulled in only by the duplicate first.
+    Weight,
+    /// Furthest behind the newest release first.
+    VersionsBehind,
+    /// Furthest from a workspace member first.
+    Depth,
+}
+
+impl Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sort::Name => write!(f, "name"),
+            Sort::Users => write!(f, "users"),
+            Sort::Weight => write!(f, "weight"),
+            Sort::VersionsBehind => write!(f, "versions-behind"),
+            Sort::Depth => write!(f, "depth"),
+        }
+    }
+}
+
+impl FromStr for Sort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "name" => Ok(Sort::Name),
+            "users" => Ok(Sort::Users),
+            "weight" => Ok(Sort::Weight),
+            "versions-behind" => Ok(Sort::VersionsBehind),
+            "depth" => Ok(Sort::Depth),
+            _ => Err(Error::InvalidArgument(format!("unknown sort order `{s}`"))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Do not query crates.io for the newest version of each duplicated crate.
+    pub offline: bool,
+    /// Record every dependency path to each duplicate, within these limits.
+    /// `None` skips the search.
+    pub paths: Option<PathLimits>,
+    /// End the recorded paths at the workspace member of this name, rather than at
+    /// any member.
+    pub chain_root: Option<String>,
+    /// How to pick the copy of each crate that is not reported.
+    pub canonical: Canonical,
+    /// Only report duplicates of these kinds. `None` reports every kind.
+    pub only: Option<BTreeSet<DuplicateKind>>,
+    /// Look up whether newer releases of each user depend on the canonical version.
+    pub check_upgrades: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Duplicate {
+    pub package: String,
+    pub version: String,
+    pub source: Option<SourceId>,
+    pub kind: DuplicateKind,
+    /// The version this copy would be unified onto, or `None` under [`Canonical::None`].
+    pub canonical: Option<String>,
+    pub latest: String,
+    pub users: Vec<Package>,
+    /// Kinds of the edges that bring this version in. Empty when the graph was
+    /// read from `Cargo.lock`, which does not record them.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub kinds: BTreeSet<DepKind>,
+    /// Every path from a direct user up to a workspace member, when requested
+    /// through [`Options::paths`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<Vec<String>>,
+    /// Whether upgrading each user would drop this copy, when requested through
+    /// [`Options::check_upgrades`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verdicts: Vec<Verdict>,
+    /// How many packages are in the graph only because of this copy.
+    #[serde(default)]
+    pub weight: usize,
+    /// The packages counted by `weight`, as `name version`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclusive: Vec<String>,
+    /// The fewest dependency edges from a workspace member to this copy, or `None`
+    /// when no member depends on it.
+    #[serde(default)]
+    pub depth: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub duplicates: Vec<Duplicate>,
+    /// Duplicates left out by the ignore list in `Cargo.toml`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppressed: Vec<Suppressed>,
+    /// Baseline entries that are no longer duplicated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed: Vec<BaselineEntry>,
+}
+
+/// Orders sources by preference when the same version comes from several of them.
+fn source_rank(source: &Option<SourceId>) -> u8 {
+    match source {
+        Some(source) if source.is_default_registry() => 0,
+        Some(source) if source.is_registry() => 1,
+        Some(_) => 2,
+        None => 3,
+    }
+}
+
+/// Picks the copy of a crate to keep according to `policy`. Among several sources
+/// of the chosen version, the registry copy is preferred.
+fn find_canonical<'a>(
+    infos: &'a [PackageInfo],
+    policy: Canonical,
+    latest: &Version,
+) -> Option<&'a PackageInfo> {
+    let highest = infos.iter().map(|info| &info.id.version).max()?;
+    let version = match policy {
+        Canonical::Highest => highest,
+        Canonical::MostUsed => {
+            let mut users: HashMap<&Version, usize> = HashMap::new();
+            for info in infos {
+                *users.entry(&info.id.version).or_default() += info.users.len();
+            }
+            // Ties go to the higher version
+            users
+                .into_iter()
+                .max_by_key(|(version, users)| (*users, *version))
+                .map(|(version, _)| version)?
+        }
+        Canonical::LatestRegistry => infos
+            .iter()
+            .map(|info| &info.id.version)
+            .find(|version| *version == latest)
+            .unwrap_or(highest),
+        Canonical::None => return None,
+    };
+    infos
+        .iter()
+        .filter(|info| info.id.version == *version)
+        .min_by_key(|info| source_rank(&info.id.source))
+}
+
+/// Lists every copy of a crate other than its canonical one, as chosen by
+/// [`Options::canonical`], sorted by package name.
+pub async fn find_duplicates(
+    package_map: &PackageMap,
+    options: &Options,
+) -> Result<Vec<Duplicate>> {
+    // sort by package name
+    let mut keys: Vec<&String> = package_map.keys().collect();
+    keys.sort();
+    let mut duplicates = vec![];
+    let client = new_client()?;
+    let mut index = Index::new(client.clone(), options.offline);
+    for key in keys {
+        let value = &package_map[key];
+        if value.len() > 1 {
+            // Find the latest version
+            let default_version = value.iter().map(|info| &info.id.version).max().unwrap();
+            let latest = if options.offline {
+                default_version.clone()
+            } else {
+                match get_latest_version(&client, key).await {
+                    Ok(latest) => Version::parse(&latest)?,
+                    Err(_) => default_version.clone(),
+                }
+            };
+            let canonical = find_canonical(value, options.canonical, &latest);
+
+            for info in value {
+                if canonical.is_some_and(|canonical| info.id == canonical.id) {
+                    continue;
+                }
+                // Without a canonical copy, judge against the best of the others
+                let reference = canonical.or_else(|| {
+                    value
+                        .iter()
+                        .filter(|other| other.id != info.id)
+                        .max_by_key(|other| {
+                            (&other.id.version, Reverse(source_rank(&other.id.source)))
+                        })
+                });
+                let kind = match reference {
+                    Some(reference) => {
+                        DuplicateKind::classify(&info.id.version, &reference.id.version)
+                    }
+                    None => continue,
+                };
+                if options
+                    .only
+                    .as_ref()
+                    .is_some_and(|only| !only.contains(&kind))
+                {
+                    continue;
+                }
+                let mut verdicts = vec![];
+                if let (true, Some(canonical)) = (options.check_upgrades, canonical) {
+                    if kind != DuplicateKind::DifferentSource {
+                        for user in &info.users {
+                            verdicts.push(
+                                check_user(&mut index, key, &canonical.id.version, user).await,
+                            );
+                        }
+                    }
+                }
+                let exclusive = get_exclusive_subtree(package_map, &info.id);
+                duplicates.push(Duplicate {
+                    package: key.clone(),
+                    version: info.id.version.to_string(),
+                    source: info.id.source.clone(),
+                    kind,
+                    canonical: canonical.map(|canonical| canonical.id.version.to_string()),
+                    latest: latest.to_string(),
+                    users: info.users.clone(),
+                    kinds: info.kinds.clone(),
+                    paths: match options.paths {
+                        Some(limits) => get_usage_paths(
+                            package_map,
+                            &info.id,
+                            limits,
+                            options.chain_root.as_deref(),
+                        ),
+                        None => vec![],
+                    },
+                    verdicts,
+                    weight: exclusive.len(),
+                    exclusive,
+                    depth: get_depth(package_map, &info.id),
+                });
+            }
+        }
+    }
+    Ok(duplicates)
+}
+
+/// How far `version` is behind `latest`, as differences in major, minor and patch.
+pub fn versions_behind(version: &str, latest: &str) -> (i64, i64, i64) {
+    match (Version::parse(version), Version::parse(latest)) {
+        (Ok(version), Ok(latest)) => (
+            latest.major as i64 - version.major as i64,
+            latest.minor as i64 - version.minor as i64,
+            latest.patch as i64 - version.patch as i64,
+        ),
+        _ => (0, 0, 0),
+    }
+}
+
+/// Sorts duplicates by `sort`, worst first. [`find_duplicates`] returns them in name
+/// order, and the sorts are stable, so ties stay in name order.
+pub fn sort_duplicates(duplicates: &mut [Duplicate], sort: Sort) {
+    match sort {
+        Sort::Name => {}
+        Sort::Users => duplicates.sort_by_key(|duplicate| Reverse(duplicate.users.len())),
+        Sort::Weight => duplicates.sort_by_key(|duplicate| Reverse(duplicate.weight)),
+        Sort::VersionsBehind => duplicates.sort_by_key(|duplicate| {
+            Reverse(versions_behind(&duplicate.version, &duplicate.latest))
+        }),
+        Sort::Depth => duplicates.sort_by_key(|duplicate| Reverse(duplicate.depth)),
+    }
+}
+
+/// Runs the whole analysis over a parsed lockfile.
+pub async fn analyze(lockfile: &Lockfile, options: &Options) -> Result<Response> {
+    let package_map = build_package_map(&Graph::from(lockfile))?;
+    let duplicates = find_duplicates(&package_map, options).await?;
+    Ok(Response {
+        duplicates,
+        suppressed: vec![],
+        fixed: vec![],
+    })
+}
+
+/// Runs the whole analysis over the resolved graph reported by `cargo metadata`.
+pub async fn analyze_metadata(
+    metadata: &Metadata,
+    filter: &Filter,
+    options: &Options,
+) -> Result<Response> {
+    let package_map = build_package_map(&metadata.graph(filter)?)?;
+    let duplicates = find_duplicates(&package_map, options).await?;
+    Ok(Response {
+        duplicates,
+        suppressed: vec![],
+        fixed: vec![],
+    })
+}
@@ -1,79 +1,43 @@
-use anyhow::bail;
-use cargo_lock::{Lockfile, Package};
-use clap::{Parser, ValueEnum};
+use anyhow::{bail, Context};
+use cargo_duplicated_deps::cfg::Target;
+use cargo_duplicated_deps::config::{today, Config};
+use cargo_duplicated_deps::git::{log, show_file, toplevel};
+use cargo_duplicated_deps::history::to_csv;
+use cargo_duplicated_deps::junit::to_junit;
+use cargo_duplicated_deps::markdown::{diff_to_markdown, to_markdown};
+use cargo_duplicated_deps::sarif::to_sarif;
+use cargo_duplicated_deps::{
+    build_package_map, build_plan, diff_duplicates, find_duplicates, fix_lockfile, get_usage_chain,
+    new_client, parse_duplicate_kinds, parse_edge_kinds, sort_duplicates, Baseline, Canonical,
+    Diagram, Duplicate, DuplicateDiff, DuplicateKind, Features, Filter, FixReport, Graph,
+    HistoryPoint, Index, Metadata, Options, PackageMap, PathLimits, Plan, Policy, PolicyReport,
+    Response, Sort,
+};
+use cargo_lock::Lockfile;
+use clap::{Parser, Subcommand, ValueEnum};
 use crossterm::execute;
 use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
-use reqwest::Client;
-use semver::Version;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fmt::Display;
-use std::io::{stdout, IsTerminal};
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::str::FromStr;
 
-async fn get_latest_version(client: &Client, package: &str) -> anyhow::Result<String> {
-    let url = format!("https://crates.io/api/v1/crates/{package}");
-    let response = client.execute(client.get(&url).build()?).await?;
-    let json: serde_json::Value = response.json().await?;
-    let latest_version = json["crate"]["newest_version"]
-        .as_str()
-        .ok_or(anyhow::anyhow!("No version found"))?;
-    Ok(latest_version.to_string())
-}
-
-#[derive(Clone, Debug)]
-struct PackageInfo {
-    version: String,
-    users: Vec<Package>,
-}
-
-fn get_usage_chain(package_map: &HashMap<String, Vec<PackageInfo>>, package: &Package) -> String {
-    let mut chain = vec![format!(
-        "{} v{}",
-        package.name.as_str(),
-        package.version.to_string()
-    )];
-    let mut current = package_map
-        .get(package.name.as_str())
-        .unwrap()
-        .iter()
-        .find(|info| info.version == package.version.to_string())
-        .unwrap();
-    loop {
-        let next = current.users.iter().find(|user| {
-            if let Some(info) = package_map.get(user.name.as_str()) {
-                if info
-                    .iter()
-                    .any(|info| info.version == user.version.to_string())
-                {
-                    current = package_map
-                        .get(user.name.as_str())
-                        .unwrap()
-                        .iter()
-                        .find(|info| info.version == user.version.to_string())
-                        .unwrap();
-                    chain.push(format!("{} v{}", user.name.as_str(), user.version));
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        });
-        if next.is_none() {
-            break;
-        }
-    }
-    chain.join(" -> ")
-}
-
 #[derive(Clone, Debug, Default, ValueEnum)]
 enum Output {
     #[default]
     Text,
     Json,
+    /// SARIF 2.1.0, for code scanning dashboards
+    Sarif,
+    /// A Markdown table, for pull request comments
+    Markdown,
+    /// A GraphViz DOT graph of the duplicates and their paths to workspace members
+    Dot,
+    /// A Mermaid flowchart of the duplicates and their paths to workspace members
+    Mermaid,
+    /// JUnit XML, with a failing test case per duplicated crate
+    Junit,
 }
 
 impl Display for Output {
@@ -81,175 +45,934 @@ impl Display for Output {
         match self {
             Output::Text => write!(f, "text"),
             Output::Json => write!(f, "json"),
+            Output::Sarif => write!(f, "sarif"),
+            Output::Markdown => write!(f, "markdown"),
+            Output::Dot => write!(f, "dot"),
+            Output::Mermaid => write!(f, "mermaid"),
+            Output::Junit => write!(f, "junit"),
         }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Duplicate {
-    pub package: String,
-    pub version: String,
-    pub latest: String,
-    pub users: Vec<Package>,
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum Paths {
+    /// One usage chain per user
+    #[default]
+    First,
+    /// Every path from a workspace member
+    All,
+}
+
+impl Display for Paths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Paths::First => write!(f, "first"),
+            Paths::All => write!(f, "all"),
+        }
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Response {
-    pub duplicates: Vec<Duplicate>,
+#[derive(Subcommand)]
+enum Command {
+    /// Compare the duplicates of two lockfiles
+    Diff { old: PathBuf, new: PathBuf },
+    /// Analyze every commit that changed the lockfile, printing CSV, or JSON with
+    /// `--output json`
+    History,
 }
 
 #[derive(Parser)]
 struct Arguments {
     _call: Option<String>,
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, global = true)]
     path: Option<PathBuf>,
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     color: Option<bool>,
-    #[arg(long)]
+    #[arg(long, global = true)]
     offline: bool,
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
-    #[arg(long, default_value_t = Output::Text)]
+    #[arg(long, global = true, default_value_t = Output::Text)]
     output: Output,
+    /// Analyze the resolved build graph from `cargo metadata` instead of `Cargo.lock`
+    #[arg(long)]
+    metadata: bool,
+    /// Read `cargo metadata --format-version 1` output from a file
+    #[arg(long, conflicts_with = "path")]
+    metadata_file: Option<PathBuf>,
+    /// Path to the `Cargo.toml` passed to `cargo metadata`
+    #[arg(long, conflicts_with = "path")]
+    manifest_path: Option<PathBuf>,
+    /// Only report packages that are built for this target triple; also accepts
+    /// `host` and `all`. Implies `--metadata`
+    #[arg(long, conflicts_with = "path")]
+    target: Vec<String>,
+    /// Dependency kinds to follow, like `cargo tree --edges`: any of `normal`, `build`,
+    /// `dev`, `all`, `no-build` and `no-dev`, comma separated. Implies `--metadata`
+    #[arg(short, long, conflicts_with = "path", value_delimiter = ',')]
+    edges: Vec<String>,
+    /// Space or comma separated list of features to activate. Implies `--metadata`
+    #[arg(short = 'F', long, conflicts_with = "path")]
+    features: Vec<String>,
+    /// Activate all available features. Implies `--metadata`
+    #[arg(long, conflicts_with = "path")]
+    all_features: bool,
+    /// Do not activate the `default` feature. Implies `--metadata`
+    #[arg(long, conflicts_with = "path")]
+    no_default_features: bool,
+    /// Only analyze the dependencies of these workspace members. Implies `--metadata`
+    #[arg(long = "package", conflicts_with = "path")]
+    packages: Vec<String>,
+    /// How many dependency paths to show for each duplicate
+    #[arg(long, default_value_t = Paths::First)]
+    paths: Paths,
+    /// With `--paths all`, the most paths listed per duplicate
+    #[arg(long, default_value_t = 100)]
+    max_paths: usize,
+    /// With `--paths all`, skip paths that go through more packages than this
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// End usage chains at this workspace member
+    #[arg(long)]
+    chain_root: Option<String>,
+    /// Which copy of a crate to keep: `highest`, `most-used`, `latest-registry`, or
+    /// `none` to list every copy
+    #[arg(long, global = true, default_value_t = Canonical::Highest)]
+    canonical: Canonical,
+    /// Only report duplicates of these kinds: `compatible`, `major`, `zero-minor`,
+    /// `prerelease`, `different-source`, or `incompatible` for all but `compatible`
+    #[arg(long, global = true, value_delimiter = ',')]
+    only: Vec<String>,
+    /// Check whether newer releases of each user depend on the canonical version
+    #[arg(long)]
+    check_upgrades: bool,
+    /// Print an ordered list of `cargo update --precise` commands that remove
+    /// duplicates instead of the report. Implies `--check-upgrades`
+    #[arg(long)]
+    plan: bool,
+    /// Write the fix plan to this file as a shell script. Implies `--check-upgrades`
+    #[arg(long)]
+    emit_fix_script: Option<PathBuf>,
+    /// Rewrite `Cargo.lock` so semver compatible duplicates use their canonical
+    /// version, where every user's requirement allows it
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "metadata", "metadata_file", "manifest_path", "target", "edges", "features",
+            "all_features", "no_default_features", "packages", "plan",
+        ]
+    )]
+    fix: bool,
+    /// Order of the reported duplicates: `name`, or worst first by `users`, `weight`,
+    /// `versions-behind` or `depth`
+    #[arg(long, default_value_t = Sort::Name)]
+    sort: Sort,
+    /// Reverse the order given by `--sort`
+    #[arg(long)]
+    reverse: bool,
+    /// Only report the first N duplicates, after sorting
+    #[arg(long)]
+    top: Option<usize>,
+    /// Exit with status 1 if any duplicate is reported
+    #[arg(long)]
+    deny: bool,
+    /// Exit with status 1 if more than N duplicates are reported
+    #[arg(long)]
+    max_duplicates: Option<usize>,
+    /// Exit with status 1 if a duplicate of one of these kinds is reported; takes the
+    /// same kinds as `--only`
+    #[arg(long, value_delimiter = ',')]
+    deny_kind: Vec<String>,
+    /// Record the reported duplicates in this file, for use with `--baseline`
+    #[arg(long)]
+    write_baseline: Option<PathBuf>,
+    /// Only fail on duplicates that are not in this baseline file, and list the
+    /// baseline entries that have been fixed
+    #[arg(long)]
+    baseline: Option<PathBuf>,
+    /// Read the lockfile as it was at this git revision instead of from the working
+    /// tree
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "metadata", "metadata_file", "manifest_path", "target", "edges", "features",
+            "all_features", "no_default_features", "packages", "fix",
+        ]
+    )]
+    rev: Option<String>,
+    /// Compare the lockfile at this git revision with the working tree, or with
+    /// `--rev`, like the `diff` command
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "metadata", "metadata_file", "manifest_path", "target", "edges", "features",
+            "all_features", "no_default_features", "packages", "fix",
+        ]
+    )]
+    base: Option<String>,
 }
 
-#[tokio::main]
-async fn run() -> anyhow::Result<()> {
-    color_eyre::install().map_err(|e| anyhow::anyhow!(e))?;
-    let args = Arguments::parse();
-    let path = args.path.unwrap_or_else(|| PathBuf::from("Cargo.lock"));
-    if args.verbose {
-        println!("Reading lockfile from {}", path.display());
+/// Standard output that treats a closed pipe, as in `| head`, as the reader having
+/// seen enough rather than as an error, so the exit code still reflects the policies.
+struct Stdout(std::io::Stdout);
+
+fn stdout() -> Stdout {
+    Stdout(std::io::stdout())
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.0.write(buf) {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(buf.len()),
+            result => result,
+        }
     }
-    if !path.exists() {
-        bail!("{} does not exist", path.display());
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.0.flush() {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
     }
+}
 
-    let lockfile = Lockfile::from_str(&tokio::fs::read_to_string(path).await?)?;
+/// How a run ended. `main` turns this into the exit code: 0 when clean, 1 on a
+/// policy violation, and 2 for errors.
+enum Status {
+    Clean,
+    Violation,
+}
 
-    let mut package_map: HashMap<String, Vec<PackageInfo>> = HashMap::new();
+fn resolve_targets(names: &[String]) -> anyhow::Result<Option<Vec<Target>>> {
+    if names.is_empty() || names.iter().any(|name| name == "all") {
+        return Ok(None);
+    }
+    let targets = names
+        .iter()
+        .map(|name| match name.as_str() {
+            "host" => Target::from_rustc(None),
+            triple => Target::from_rustc(Some(triple)),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Some(targets))
+}
 
-    // Pass 1: insert package versions
-    for package in &lockfile.packages {
-        let info = PackageInfo {
-            version: package.version.to_string(),
-            users: vec![],
-        };
-        if let Some(s) = package_map.get_mut(package.name.as_str()) {
-            s.push(info);
-        } else {
-            package_map.insert(package.name.to_string(), vec![info]);
+fn print_fix(report: &FixReport, path: &Path, output: &Output) -> anyhow::Result<()> {
+    if let Output::Json = output {
+        writeln!(stdout(), "{}", serde_json::to_string_pretty(report)?)?;
+        return Ok(());
+    }
+    if report.is_empty() {
+        writeln!(stdout(), "{} was not changed", path.display())?;
+    } else {
+        writeln!(stdout(), "Updated {}:", path.display())?;
+        for unification in &report.unified {
+            for user in &unification.users {
+                writeln!(
+                    stdout(),
+                    "  ~ {user}: {} {} -> {}",
+                    unification.package,
+                    unification.from,
+                    unification.to
+                )?;
+            }
+        }
+        for removed in &report.removed {
+            writeln!(stdout(), "  - {removed}")?;
         }
     }
+    if !report.refused.is_empty() {
+        writeln!(stdout(), "Refused:")?;
+        for refusal in &report.refused {
+            writeln!(
+                stdout(),
+                "  {} {} -> {}: {}",
+                refusal.package,
+                refusal.from,
+                refusal.to,
+                refusal.reason
+            )?;
+        }
+    }
+    Ok(())
+}
 
-    // Pass 2: insert users
-    for package in &lockfile.packages {
-        for dep in &package.dependencies {
-            if let Some(s) = package_map.get_mut(dep.name.as_str()) {
-                for info in s.iter_mut() {
-                    if info.version == dep.version.to_string() {
-                        info.users.push(package.clone());
-                    }
-                }
-            } else {
-                println!("ERROR: {} not found", dep.name);
-            }
+fn print_plan(plan: &Plan, output: &Output) -> anyhow::Result<()> {
+    if let Output::Json = output {
+        writeln!(stdout(), "{}", serde_json::to_string_pretty(plan)?)?;
+        return Ok(());
+    }
+    if plan.steps.is_empty() {
+        writeln!(stdout(), "No safe `cargo update` commands were found")?;
+    }
+    for (i, step) in plan.steps.iter().enumerate() {
+        writeln!(
+            stdout(),
+            "{}. {}  # removes {}",
+            i + 1,
+            step.command,
+            step.removes
+        )?;
+    }
+    if !plan.skipped.is_empty() {
+        writeln!(stdout(), "Skipped:")?;
+        for skipped in &plan.skipped {
+            writeln!(
+                stdout(),
+                "  - {} {} -> {}: {}",
+                skipped.package,
+                skipped.from,
+                skipped.to,
+                skipped.reason
+            )?;
         }
     }
+    Ok(())
+}
 
-    // sort by package name
-    let mut keys: Vec<String> = package_map.keys().cloned().collect();
-    keys.sort();
-    let mut duplicates = vec![];
-    let client = Client::builder()
-        .user_agent("cargo-duplicated-deps")
-        .build()?;
-    for key in keys {
-        let value = package_map.get(key.as_str()).unwrap();
-        if value.len() > 1 {
-            // Find the latest version
-            let default_version = value
-                .iter()
-                .max_by_key(|info| Version::parse(&info.version).unwrap())
-                .unwrap()
-                .version
-                .clone();
-            let latest = if args.offline {
-                default_version.clone()
-            } else {
-                get_latest_version(&client, &key)
-                    .await
-                    .unwrap_or(default_version.clone())
-            };
-            let default_version = Version::parse(&default_version)?;
-            let latest = Version::parse(&latest)?;
-
-            for info in value {
-                if Version::parse(&info.version)? != default_version {
-                    let mut dup_info = Duplicate {
-                        package: key.clone(),
-                        version: info.version.clone(),
-                        latest: latest.to_string(),
-                        users: vec![],
-                    };
-                    for user in &info.users {
-                        dup_info.users.push(user.clone());
-                    }
-                    duplicates.push(dup_info);
-                }
+/// Reads the lockfile at `path`, from the commit `rev` when given and from the
+/// working tree otherwise.
+async fn read_lockfile(path: &Path, rev: Option<&str>, verbose: bool) -> anyhow::Result<Lockfile> {
+    Ok(Lockfile::from_str(
+        &read_lockfile_text(path, rev, verbose).await?,
+    )?)
+}
+
+async fn read_lockfile_text(
+    path: &Path,
+    rev: Option<&str>,
+    verbose: bool,
+) -> anyhow::Result<String> {
+    Ok(match rev {
+        Some(rev) => {
+            if verbose {
+                writeln!(
+                    stdout(),
+                    "Reading lockfile from {} at {rev}",
+                    path.display()
+                )?;
             }
+            show_file(rev, path)?
         }
+        None => {
+            if verbose {
+                writeln!(stdout(), "Reading lockfile from {}", path.display())?;
+            }
+            if !path.exists() {
+                bail!("{} does not exist", path.display());
+            }
+            tokio::fs::read_to_string(path).await?
+        }
+    })
+}
+
+/// How SARIF results refer to the file at `path`: relative to the root of its git
+/// repository, which is what code scanning resolves them against, or as a `file:`
+/// URI outside of one.
+fn sarif_uri(path: &Path) -> anyhow::Result<String> {
+    // The file itself may only exist at an earlier revision
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .context("the lockfile path has no file name")?;
+    let path = dir
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", dir.display()))?
+        .join(name);
+    if let Ok(root) = toplevel(&path) {
+        if let Ok(relative) = path.strip_prefix(root.canonicalize()?) {
+            return Ok(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace('%', "%25")
+        .replace(' ', "%20");
+    // Windows paths start with a drive letter rather than a slash
+    if path.starts_with('/') {
+        Ok(format!("file://{path}"))
+    } else {
+        Ok(format!("file:///{path}"))
+    }
+}
+
+/// Renders duplicates as SARIF, locating each one in the lockfile at `path`.
+fn sarif(
+    duplicates: &[Duplicate],
+    package_map: &PackageMap,
+    path: &Path,
+    lockfile: Option<&str>,
+    chain_root: Option<&str>,
+) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(&to_sarif(
+        duplicates,
+        package_map,
+        &sarif_uri(path)?,
+        lockfile,
+        chain_root,
+    ))?)
+}
+
+/// Reads the ignore list, warning about entries that have expired.
+fn load_config(manifest_path: &Path) -> anyhow::Result<Config> {
+    let config = Config::load(manifest_path)?;
+    for entry in config.expired(&today()) {
+        eprintln!(
+            "warning: the ignore entry for `{}` in {} expired on {}",
+            entry.spec(),
+            manifest_path.display(),
+            entry.until().unwrap_or_default()
+        );
     }
+    Ok(config)
+}
 
+/// One line describing a duplicate, without the usage chains.
+fn summary(duplicate: &Duplicate) -> String {
+    format!(
+        "{} v{} ({}) used by {} {}",
+        duplicate.package,
+        duplicate.version,
+        duplicate.kind,
+        duplicate.users.len(),
+        if duplicate.users.len() == 1 {
+            "package"
+        } else {
+            "packages"
+        }
+    )
+}
+
+fn print_diff(diff: &DuplicateDiff, output: &Output) -> anyhow::Result<()> {
+    if let Output::Json = output {
+        writeln!(stdout(), "{}", serde_json::to_string_pretty(diff)?)?;
+        return Ok(());
+    }
+    if let Output::Markdown = output {
+        write!(stdout(), "{}", diff_to_markdown(diff))?;
+        return Ok(());
+    }
+    if diff.is_empty() {
+        writeln!(stdout(), "No duplicates were added, removed or changed")?;
+    }
+    for duplicate in &diff.added {
+        writeln!(stdout(), "+ {}", summary(duplicate))?;
+    }
+    for duplicate in &diff.removed {
+        writeln!(stdout(), "- {}", summary(duplicate))?;
+    }
+    for changed in &diff.changed {
+        writeln!(stdout(), "~ {}", summary(&changed.after))?;
+        for change in &changed.changes {
+            writeln!(stdout(), "  - {change}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Analyzes two lockfiles with the same options and prints how their duplicates differ.
+/// The ignore list is read from the `Cargo.toml` next to the new lockfile.
+///
+/// Each side is a lockfile path, with the commit to read it from or `None` for the
+/// working tree.
+async fn run_diff(
+    args: &Arguments,
+    options: &Options,
+    old: (&Path, Option<&str>),
+    new: (&Path, Option<&str>),
+) -> anyhow::Result<Status> {
+    let config = load_config(&new.0.with_file_name("Cargo.toml"))?;
+    let today = today();
+    let mut sides = vec![];
+    for (path, rev) in [old, new] {
+        let text = read_lockfile_text(path, rev, args.verbose).await?;
+        let package_map = build_package_map(&Graph::from(&Lockfile::from_str(&text)?))?;
+        let (duplicates, _) = config.apply(find_duplicates(&package_map, options).await?, &today);
+        sides.push((duplicates, package_map, text));
+    }
+    let (new_duplicates, package_map, text) = sides.pop().unwrap_or_default();
+    let (old_duplicates, _, _) = sides.pop().unwrap_or_default();
+    let diff = diff_duplicates(old_duplicates, new_duplicates);
+    // Code scanning and test dashboards track findings themselves, and a graph of what
+    // went away says little, so those formats only report what is new or changed
+    let findings: Vec<Duplicate> = diff
+        .added
+        .iter()
+        .chain(diff.changed.iter().map(|changed| &changed.after))
+        .cloned()
+        .collect();
+    match args.output {
+        Output::Text | Output::Json | Output::Markdown => print_diff(&diff, &args.output)?,
+        Output::Sarif => writeln!(
+            stdout(),
+            "{}",
+            sarif(
+                &findings,
+                &package_map,
+                new.0,
+                Some(&text),
+                args.chain_root.as_deref()
+            )?
+        )?,
+        Output::Dot => write!(
+            stdout(),
+            "{}",
+            Diagram::explain(&findings, &package_map).to_dot()
+        )?,
+        Output::Mermaid => write!(
+            stdout(),
+            "{}",
+            Diagram::explain(&findings, &package_map).to_mermaid()
+        )?,
+        Output::Junit => write!(
+            stdout(),
+            "{}",
+            to_junit(&findings, &[], &package_map, args.chain_root.as_deref())
+        )?,
+    }
+    Ok(Status::Clean)
+}
+
+/// Runs the analysis on each commit that changed the lockfile. Newest versions are
+/// not looked up, which keeps hundreds of revisions fast.
+async fn run_history(args: &Arguments, options: &Options) -> anyhow::Result<Status> {
+    let path = args.path.as_deref().unwrap_or(Path::new("Cargo.lock"));
+    let config = load_config(&path.with_file_name("Cargo.toml"))?;
+    let today = today();
+    let options = Options {
+        offline: true,
+        paths: None,
+        check_upgrades: false,
+        ..options.clone()
+    };
+    let mut points = vec![];
+    for revision in log(path)? {
+        let lockfile = match read_lockfile(path, Some(&revision.commit), args.verbose).await {
+            Ok(lockfile) => lockfile,
+            Err(e) => {
+                eprintln!("warning: skipping {}: {e:#}", revision.commit);
+                continue;
+            }
+        };
+        let package_map = build_package_map(&Graph::from(&lockfile))?;
+        let (duplicates, _) = config.apply(find_duplicates(&package_map, &options).await?, &today);
+        points.push(HistoryPoint::new(&revision, &duplicates));
+    }
     if let Output::Json = args.output {
-        let response = Response { duplicates };
-        println!("{}", serde_json::to_string_pretty(&response)?);
+        writeln!(stdout(), "{}", serde_json::to_string_pretty(&points)?)?;
     } else {
-        let color = args.color.unwrap_or(stdout().is_terminal());
-        for duplicate in duplicates {
-            let package_text = if duplicate.users.len() == 1 {
-                "package"
-            } else {
-                "packages"
+        write!(stdout(), "{}", to_csv(&points))?;
+    }
+    Ok(Status::Clean)
+}
+
+#[tokio::main]
+async fn run() -> anyhow::Result<Status> {
+    let args = Arguments::parse();
+    let targets = resolve_targets(&args.target)?;
+    let filter = Filter {
+        host: match targets {
+            Some(_) => Some(Target::from_rustc(None)?),
+            None => None,
+        },
+        targets,
+        kinds: if args.edges.is_empty() {
+            None
+        } else {
+            Some(parse_edge_kinds(&args.edges)?)
+        },
+        packages: args.packages.clone(),
+    };
+    let features = Features {
+        features: args
+            .features
+            .iter()
+            .flat_map(|list| list.split([' ', ',']))
+            .filter(|feature| !feature.is_empty())
+            .map(String::from)
+            .collect(),
+        all_features: args.all_features,
+        no_default_features: args.no_default_features,
+    };
+    let options = Options {
+        offline: args.offline,
+        paths: match args.paths {
+            Paths::First => None,
+            Paths::All => Some(PathLimits {
+                max_paths: Some(args.max_paths),
+                max_depth: args.max_depth,
+            }),
+        },
+        chain_root: args.chain_root.clone(),
+        canonical: args.canonical,
+        only: if args.only.is_empty() {
+            None
+        } else {
+            Some(parse_duplicate_kinds(&args.only)?)
+        },
+        check_upgrades: args.check_upgrades || args.plan || args.emit_fix_script.is_some(),
+    };
+
+    if let Some(Command::Diff { old, new }) = &args.command {
+        return run_diff(&args, &options, (old, None), (new, None)).await;
+    }
+    if let Some(Command::History) = &args.command {
+        return run_history(&args, &options).await;
+    }
+    if let Some(base) = &args.base {
+        let path = args.path.as_deref().unwrap_or(Path::new("Cargo.lock"));
+        return run_diff(
+            &args,
+            &options,
+            (path, Some(base)),
+            (path, args.rev.as_deref()),
+        )
+        .await;
+    }
+
+    let use_metadata = args.metadata
+        || args.manifest_path.is_some()
+        || !args.target.is_empty()
+        || !args.edges.is_empty()
+        || !args.features.is_empty()
+        || args.all_features
+        || args.no_default_features
+        || !args.packages.is_empty();
+    let mut lockfile = None;
+    // The `Cargo.toml` holding the ignore list
+    let manifest_path;
+    let lock_path: PathBuf;
+    let graph = if let Some(path) = &args.metadata_file {
+        if args.verbose {
+            writeln!(stdout(), "Reading cargo metadata from {}", path.display())?;
+        }
+        let metadata = Metadata::load(path)?;
+        manifest_path = metadata.workspace_root.join("Cargo.toml");
+        lock_path = metadata.workspace_root.join("Cargo.lock");
+        metadata.graph(&filter)?
+    } else if use_metadata {
+        if args.verbose {
+            writeln!(stdout(), "Running cargo metadata")?;
+        }
+        let metadata = Metadata::from_cargo(args.manifest_path.as_deref(), &features)?;
+        manifest_path = metadata.workspace_root.join("Cargo.toml");
+        lock_path = metadata.workspace_root.join("Cargo.lock");
+        metadata.graph(&filter)?
+    } else {
+        let path = args.path.unwrap_or_else(|| PathBuf::from("Cargo.lock"));
+        let parsed = read_lockfile(&path, args.rev.as_deref(), args.verbose).await?;
+        manifest_path = path.with_file_name("Cargo.toml");
+        lock_path = path.clone();
+        let graph = Graph::from(&parsed);
+        lockfile = Some((path, parsed));
+        graph
+    };
+    if let Some(root) = &args.chain_root {
+        if !graph
+            .packages
+            .iter()
+            .any(|package| package.source.is_none() && package.name.as_str() == root)
+        {
+            bail!("{root} is not a workspace member");
+        }
+    }
+    let package_map = build_package_map(&graph)?;
+    let workspace_root = match manifest_path.parent() {
+        Some(root) if !root.as_os_str().is_empty() => root.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let config = load_config(&manifest_path)?;
+    let today = today();
+    let (mut duplicates, suppressed) =
+        config.apply(find_duplicates(&package_map, &options).await?, &today);
+
+    if args.fix {
+        let Some((path, mut lockfile)) = lockfile else {
+            bail!("--fix only works on Cargo.lock");
+        };
+        let report = fix_lockfile(&mut lockfile, &duplicates, &workspace_root);
+        if !report.is_empty() {
+            tokio::fs::write(&path, lockfile.to_string()).await?;
+        }
+        print_fix(&report, &path, &args.output)?;
+        return Ok(Status::Clean);
+    }
+
+    if args.plan || args.emit_fix_script.is_some() {
+        let mut index = Index::new(new_client()?, args.offline);
+        let plan = build_plan(
+            &duplicates,
+            &package_map,
+            &graph,
+            &mut index,
+            &workspace_root,
+        )
+        .await;
+        if let Some(path) = &args.emit_fix_script {
+            tokio::fs::write(path, plan.to_script()).await?;
+            if args.verbose {
+                writeln!(stdout(), "Wrote fix script to {}", path.display())?;
+            }
+        }
+        if args.plan {
+            print_plan(&plan, &args.output)?;
+            return Ok(Status::Clean);
+        }
+    }
+
+    if let Some(path) = &args.write_baseline {
+        Baseline::from_duplicates(&duplicates).save(path)?;
+        if args.verbose {
+            writeln!(stdout(), "Wrote baseline to {}", path.display())?;
+        }
+    }
+
+    // Policies look at every reported duplicate, not only the `--top` ones, and with a
+    // baseline only at the new ones
+    let policy = Policy {
+        deny: args.deny,
+        max_duplicates: args.max_duplicates,
+        deny_kinds: parse_duplicate_kinds(&args.deny_kind)?,
+        baseline: match &args.baseline {
+            Some(path) => Some(
+                Baseline::load(path)
+                    .with_context(|| format!("failed to read baseline {}", path.display()))?,
+            ),
+            None => None,
+        },
+    };
+    let PolicyReport { violations, fixed } = policy.check(&duplicates);
+
+    sort_duplicates(&mut duplicates, args.sort);
+    if args.reverse {
+        duplicates.reverse();
+    }
+    if let Some(top) = args.top {
+        duplicates.truncate(top);
+    }
+
+    match args.output {
+        Output::Json => {
+            let response = Response {
+                duplicates,
+                suppressed,
+                fixed,
             };
-            if color {
-                execute!(
+            writeln!(stdout(), "{}", serde_json::to_string_pretty(&response)?)?;
+        }
+        Output::Markdown => {
+            write!(
+                stdout(),
+                "{}",
+                to_markdown(
+                    &duplicates,
+                    &suppressed,
+                    &package_map,
+                    args.chain_root.as_deref()
+                )
+            )?;
+        }
+        Output::Dot => {
+            write!(
+                stdout(),
+                "{}",
+                Diagram::explain(&duplicates, &package_map).to_dot()
+            )?;
+        }
+        Output::Mermaid => {
+            write!(
+                stdout(),
+                "{}",
+                Diagram::explain(&duplicates, &package_map).to_mermaid()
+            )?;
+        }
+        Output::Junit => {
+            write!(
+                stdout(),
+                "{}",
+                to_junit(
+                    &duplicates,
+                    &suppressed,
+                    &package_map,
+                    args.chain_root.as_deref()
+                )
+            )?;
+        }
+        Output::Sarif => {
+            let text = read_lockfile_text(&lock_path, args.rev.as_deref(), false)
+                .await
+                .ok();
+            writeln!(
+                stdout(),
+                "{}",
+                sarif(
+                    &duplicates,
+                    &package_map,
+                    &lock_path,
+                    text.as_deref(),
+                    args.chain_root.as_deref()
+                )?
+            )?;
+        }
+        Output::Text => {
+            let color = args.color.unwrap_or(std::io::stdout().is_terminal());
+            for duplicate in duplicates {
+                let package_text = if duplicate.users.len() == 1 {
+                    "package"
+                } else {
+                    "packages"
+                };
+                let kinds_text = if duplicate.kinds.is_empty() {
+                    String::new()
+                } else {
+                    let kinds: Vec<String> =
+                        duplicate.kinds.iter().map(|k| k.to_string()).collect();
+                    format!(" [{}]", kinds.join(", "))
+                };
+                let weight_text = match duplicate.weight {
+                    0 => String::new(),
+                    1 => ", pulls in 1 other package".to_string(),
+                    weight => format!(", pulls in {weight} other packages"),
+                };
+                let source_text = match &duplicate.source {
+                    Some(source)
+                        if duplicate.kind == DuplicateKind::DifferentSource
+                            || !source.is_default_registry() =>
+                    {
+                        format!(" ({source})")
+                    }
+                    _ => String::new(),
+                };
+                if color {
+                    execute!(
+                        stdout(),
+                        SetForegroundColor(Color::DarkCyan),
+                        Print(&duplicate.package),
+                        Print(" "),
+                        ResetColor,
+                        Print(format!("v{}", duplicate.version)),
+                        Print(&source_text),
+                        Print(" "),
+                        SetForegroundColor(if duplicate.kind == DuplicateKind::Compatible {
+                            Color::DarkGreen
+                        } else {
+                            Color::DarkRed
+                        }),
+                        Print(format!("({})", duplicate.kind)),
+                        ResetColor,
+                        Print(" "),
+                        Print("used by"),
+                        Print(" "),
+                        Print(duplicate.users.len()),
+                        Print(" "),
+                        Print(package_text),
+                        Print(&kinds_text),
+                        Print(&weight_text),
+                        Print(" "),
+                        SetForegroundColor(Color::DarkYellow),
+                        Print(format!("(available: v{})", duplicate.latest)),
+                        ResetColor,
+                    )?;
+                    writeln!(stdout())?;
+                } else {
+                    writeln!(
+                        stdout(),
+                        "{} v{}{source_text} ({}) used by {} {package_text}{kinds_text}{weight_text} (available: v{})",
+                        duplicate.package,
+                        duplicate.version,
+                        duplicate.kind,
+                        duplicate.users.len(),
+                        duplicate.latest
+                    )?;
+                }
+                if let Paths::All = args.paths {
+                    for path in &duplicate.paths {
+                        writeln!(stdout(), "  - {}", path.join(" -> "))?;
+                    }
+                } else {
+                    for user in &duplicate.users {
+                        match get_usage_chain(&package_map, user, args.chain_root.as_deref()) {
+                            Some(chain) => writeln!(stdout(), "  - {chain}")?,
+                            None => writeln!(
+                                stdout(),
+                                "  - {} v{} (not used by {})",
+                                user.name,
+                                user.version,
+                                args.chain_root.as_deref().unwrap_or("a workspace member")
+                            )?,
+                        }
+                    }
+                }
+                if !duplicate.exclusive.is_empty() {
+                    writeln!(
+                        stdout(),
+                        "  + only needed here: {}",
+                        duplicate.exclusive.join(", ")
+                    )?;
+                }
+                if let Some(canonical) = &duplicate.canonical {
+                    for verdict in &duplicate.verdicts {
+                        writeln!(
+                            stdout(),
+                            "  = {}",
+                            verdict.describe(&duplicate.package, &duplicate.version, canonical)
+                        )?;
+                    }
+                }
+            }
+            if !suppressed.is_empty() {
+                let names: Vec<String> = suppressed
+                    .iter()
+                    .map(|suppressed| format!("{} v{}", suppressed.package, suppressed.version))
+                    .collect();
+                writeln!(
                     stdout(),
-                    SetForegroundColor(Color::DarkCyan),
-                    Print(&duplicate.package),
-                    Print(" "),
-                    ResetColor,
-                    Print(format!("v{}", duplicate.version)),
-                    Print(" "),
-                    Print("used by"),
-                    Print(" "),
-                    Print(duplicate.users.len()),
-                    Print(" "),
-                    Print(package_text),
-                    Print(" "),
-                    SetForegroundColor(Color::DarkYellow),
-                    Print(format!("(available: v{})", duplicate.latest)),
-                    ResetColor,
+                    "Ignored {} {}: {}",
+                    suppressed.len(),
+                    if suppressed.len() == 1 {
+                        "duplicate"
+                    } else {
+                        "duplicates"
+                    },
+                    names.join(", ")
                 )?;
-                println!();
-            } else {
-                println!(
-                    "{} v{} used by {} {package_text} (available: v{})",
-                    duplicate.package,
-                    duplicate.version,
-                    duplicate.users.len(),
-                    duplicate.latest
-                );
             }
-            for user in &duplicate.users {
-                println!("  - {}", get_usage_chain(&package_map, user));
+            if !fixed.is_empty() {
+                let names: Vec<String> = fixed
+                    .iter()
+                    .map(|entry| format!("{} v{}", entry.package, entry.version))
+                    .collect();
+                writeln!(
+                    stdout(),
+                    "Fixed since the baseline, and can be removed from it: {}",
+                    names.join(", ")
+                )?;
             }
         }
     }
 
-    Ok(())
+    if violations.is_empty() {
+        return Ok(Status::Clean);
+    }
+    for violation in &violations {
+        eprintln!("error: {violation}");
+    }
+    Ok(Status::Violation)
 }
 
-fn main() {
-    run().unwrap();
+fn main() -> ExitCode {
+    match run() {
+        Ok(Status::Clean) => ExitCode::SUCCESS,
+        Ok(Status::Violation) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
 }
@@ -0,0 +1,186 @@
+//! The requirements a package's `Cargo.toml` places on its dependencies, for graphs
+//! that do not record them. Workspace members and other path packages are read from
+//! the workspace, registry packages from the sources cargo unpacks into
+//! `$CARGO_HOME/registry/src` and git packages from its checkouts in
+//! `$CARGO_HOME/git/checkouts`.
+
+use crate::error::{Error, Result};
+use crate::registry::cargo_home;
+use cargo_lock::Package;
+use semver::VersionReq;
+use std::path::{Path, PathBuf};
+
+/// The requirements the manifest of `user` places on `package`. Dev-dependencies
+/// only count for path packages, since they are not part of the graph of a
+/// registry or git package. Dependencies inherited with `workspace = true` take
+/// their requirement from the workspace's `[workspace.dependencies]`.
+pub fn read_requirements(
+    user: &Package,
+    package: &str,
+    workspace_root: &Path,
+) -> Result<Vec<VersionReq>> {
+    let (path, sections): (_, &[&str]) = match &user.source {
+        None => (
+            find_package(workspace_root, user).ok_or_else(|| {
+                Error::Manifest(format!(
+                    "{} {} is not in the workspace at {}",
+                    user.name,
+                    user.version,
+                    workspace_root.display()
+                ))
+            })?,
+            &["dependencies", "build-dependencies", "dev-dependencies"],
+        ),
+        Some(source) if source.is_git() => (
+            find_git_checkout(user).ok_or_else(|| {
+                Error::Manifest(format!(
+                    "{} {} is not in the local git checkouts",
+                    user.name, user.version
+                ))
+            })?,
+            &["dependencies", "build-dependencies"],
+        ),
+        Some(_) => (
+            find_registry_source(user).ok_or_else(|| {
+                Error::Manifest(format!(
+                    "{} {} is not in the local registry sources",
+                    user.name, user.version
+                ))
+            })?,
+            &["dependencies", "build-dependencies"],
+        ),
+    };
+    let manifest = parse(&path)?;
+    let mut tables = vec![&manifest];
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        tables.extend(targets.values().filter_map(toml::Value::as_table));
+    }
+    let mut requirements = vec![];
+    for table in tables {
+        for section in sections {
+            let Some(deps) = table.get(*section).and_then(toml::Value::as_table) else {
+                continue;
+            };
+            for (name, dep) in deps {
+                let inherited;
+                let dep = if dep.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+                    match workspace_dependency(&path, name) {
+                        Some(dep) => {
+                            inherited = dep;
+                            &inherited
+                        }
+                        // Assuming `*` here would let anything through
+                        None if name == package => {
+                            return Err(Error::Manifest(format!(
+                                "{} inherits {name} from a workspace, but its \
+                                 `[workspace.dependencies]` entry could not be found",
+                                path.display()
+                            )))
+                        }
+                        None => continue,
+                    }
+                } else {
+                    dep
+                };
+                let (real_name, req) = match dep {
+                    toml::Value::String(req) => (name.as_str(), Some(req.as_str())),
+                    toml::Value::Table(dep) => (
+                        dep.get("package")
+                            .and_then(toml::Value::as_str)
+                            .unwrap_or(name),
+                        dep.get("version").and_then(toml::Value::as_str),
+                    ),
+                    _ => continue,
+                };
+                if real_name == package {
+                    requirements.push(req.unwrap_or("*").parse()?);
+                }
+            }
+        }
+    }
+    Ok(requirements)
+}
+
+/// The `[workspace.dependencies]` entry for `name` in the workspace that the
+/// manifest at `path` belongs to, which is the closest one above it.
+fn workspace_dependency(path: &Path, name: &str) -> Option<toml::Value> {
+    for dir in path.parent()?.ancestors() {
+        let Ok(manifest) = parse(&dir.join("Cargo.toml")) else {
+            continue;
+        };
+        if let Some(workspace) = manifest.get("workspace") {
+            return workspace.get("dependencies")?.get(name).cloned();
+        }
+    }
+    None
+}
+
+fn parse(path: &Path) -> Result<toml::Table> {
+    std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|e| Error::Manifest(format!("failed to parse {}: {e}", path.display())))
+}
+
+fn find_registry_source(user: &Package) -> Option<PathBuf> {
+    let crate_dir = format!("{}-{}", user.name, user.version);
+    std::fs::read_dir(cargo_home()?.join("registry").join("src"))
+        .ok()?
+        .flatten()
+        .map(|dir| dir.path().join(&crate_dir).join("Cargo.toml"))
+        .find(|path| path.is_file())
+}
+
+/// Cargo checks out each locked commit of a repository into
+/// `git/checkouts/<repository>-<hash>/<short commit>`.
+fn find_git_checkout(user: &Package) -> Option<PathBuf> {
+    let rev = user.source.as_ref()?.precise()?;
+    for repository in std::fs::read_dir(cargo_home()?.join("git").join("checkouts")).ok()? {
+        let Ok(checkouts) = std::fs::read_dir(repository.ok()?.path()) else {
+            continue;
+        };
+        for checkout in checkouts.flatten() {
+            if rev.starts_with(checkout.file_name().to_string_lossy().as_ref()) {
+                if let Some(path) = find_package(&checkout.path(), user) {
+                    return Some(path);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Searches `dir` for the manifest of `package`, skipping build output and hidden
+/// directories. A manifest that inherits its version from the workspace matches any
+/// version.
+fn find_package(dir: &Path, package: &Package) -> Option<PathBuf> {
+    let path = dir.join("Cargo.toml");
+    if let Ok(manifest) = parse(&path) {
+        let section = manifest.get("package").and_then(toml::Value::as_table);
+        let name = section
+            .and_then(|section| section.get("name"))
+            .and_then(toml::Value::as_str);
+        let version = section.and_then(|section| section.get("version"));
+        let same_version = match version {
+            Some(toml::Value::String(version)) => *version == package.version.to_string(),
+            // Inherited from the workspace, or left out for 0.0.0
+            Some(_) => true,
+            None => package.version == semver::Version::new(0, 0, 0),
+        };
+        if name == Some(package.name.as_str()) && same_version {
+            return Some(path);
+        }
+    }
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && name != "target"
+        })
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    dirs.iter().find_map(|dir| find_package(dir, package))
+}
@@ -0,0 +1,134 @@
+//! Markdown output, for pasting into pull request comments.
+
+use crate::config::Suppressed;
+use crate::diff::DuplicateDiff;
+use crate::graph::{get_usage_chain, PackageMap};
+use crate::Duplicate;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("{count} {singular}")
+    } else {
+        format!("{count} {plural}")
+    }
+}
+
+fn table(out: &mut String, duplicates: &[Duplicate]) {
+    out.push_str("| Crate | Duplicate | Canonical | Latest | Kind | Users |\n");
+    out.push_str("| --- | --- | --- | --- | --- | ---: |\n");
+    for duplicate in duplicates {
+        let canonical = duplicate.canonical.as_deref().unwrap_or("-");
+        _ = writeln!(
+            out,
+            "| `{}` | {} | {canonical} | {} | {} | {} |",
+            duplicate.package,
+            duplicate.version,
+            duplicate.latest,
+            duplicate.kind,
+            duplicate.users.len()
+        );
+    }
+}
+
+/// Renders a report as a summary, a table of duplicates and a collapsible section of
+/// usage chains per duplicate.
+pub fn to_markdown(
+    duplicates: &[Duplicate],
+    suppressed: &[Suppressed],
+    package_map: &PackageMap,
+    chain_root: Option<&str>,
+) -> String {
+    let mut out = String::from("## Duplicated dependencies\n\n");
+    if duplicates.is_empty() {
+        out.push_str("No duplicated dependencies were found.\n");
+    } else {
+        let crates: BTreeSet<&str> = duplicates
+            .iter()
+            .map(|duplicate| duplicate.package.as_str())
+            .collect();
+        let weight: usize = duplicates.iter().map(|duplicate| duplicate.weight).sum();
+        _ = write!(
+            out,
+            "**{}** of {}",
+            plural(duplicates.len(), "duplicate", "duplicates"),
+            plural(crates.len(), "crate", "crates"),
+        );
+        if weight > 0 {
+            _ = write!(
+                out,
+                ", which pull in {} nothing else needs",
+                plural(weight, "package", "packages")
+            );
+        }
+        out.push_str(".\n\n");
+        table(&mut out, duplicates);
+        for duplicate in duplicates {
+            _ = writeln!(
+                out,
+                "\n<details>\n<summary><code>{} v{}</code> usage chains</summary>\n",
+                duplicate.package, duplicate.version
+            );
+            for user in &duplicate.users {
+                match get_usage_chain(package_map, user, chain_root) {
+                    Some(chain) => _ = writeln!(out, "- {chain}"),
+                    None => _ = writeln!(out, "- {} v{}", user.name, user.version),
+                }
+            }
+            out.push_str("\n</details>\n");
+        }
+    }
+    if !suppressed.is_empty() {
+        _ = writeln!(
+            out,
+            "\n{} ignored:\n",
+            plural(suppressed.len(), "duplicate is", "duplicates are")
+        );
+        for suppressed in suppressed {
+            _ = write!(out, "- `{} v{}`", suppressed.package, suppressed.version);
+            match &suppressed.reason {
+                Some(reason) => _ = writeln!(out, ": {reason}"),
+                None => out.push('\n'),
+            }
+        }
+    }
+    out
+}
+
+/// Renders a diff as tables of added and removed duplicates and a list of changes.
+pub fn diff_to_markdown(diff: &DuplicateDiff) -> String {
+    let mut out = String::from("## Duplicated dependencies\n\n");
+    if diff.is_empty() {
+        out.push_str("No duplicates were added, removed or changed.\n");
+        return out;
+    }
+    _ = writeln!(
+        out,
+        "**{} added**, {} removed, {} changed.",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    if !diff.added.is_empty() {
+        out.push_str("\n### Added\n\n");
+        table(&mut out, &diff.added);
+    }
+    if !diff.removed.is_empty() {
+        out.push_str("\n### Removed\n\n");
+        table(&mut out, &diff.removed);
+    }
+    if !diff.changed.is_empty() {
+        out.push_str("\n### Changed\n\n");
+        for changed in &diff.changed {
+            _ = writeln!(
+                out,
+                "- `{} v{}`: {}",
+                changed.after.package,
+                changed.after.version,
+                changed.changes.join(", ")
+            );
+        }
+    }
+    out
+}
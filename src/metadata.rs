@@ -0,0 +1,598 @@
+//! Analysis backend built on the resolved graph reported by `cargo metadata`.
+
+use crate::cfg::Target;
+use crate::error::{Error, Result};
+use crate::graph::{DepKind, Graph};
+use cargo_lock::{Dependency, Package, SourceId};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Output of `cargo metadata --format-version 1`.
+///
+/// Only the fields needed to rebuild the dependency graph are decoded.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<MetadataPackage>,
+    pub workspace_members: Vec<String>,
+    pub workspace_root: PathBuf,
+    pub resolve: Option<Resolve>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MetadataPackage {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+    pub manifest_path: PathBuf,
+    #[serde(default)]
+    pub dependencies: Vec<MetadataDependency>,
+    #[serde(default)]
+    pub targets: Vec<MetadataTarget>,
+}
+
+/// A library, binary or other target of a package.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MetadataTarget {
+    /// Such as `lib`, `bin` or `proc-macro`.
+    pub kind: Vec<String>,
+}
+
+/// A dependency as declared in a package's manifest.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MetadataDependency {
+    /// The real crate name, even when the dependency is renamed.
+    pub name: String,
+    pub req: VersionReq,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Resolve {
+    pub nodes: Vec<Node>,
+    pub root: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Node {
+    pub id: String,
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NodeDep {
+    pub name: String,
+    pub pkg: String,
+    #[serde(default)]
+    pub dep_kinds: Vec<DepKindInfo>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DepKindInfo {
+    /// `None` for normal dependencies, otherwise `"dev"` or `"build"`.
+    pub kind: Option<String>,
+    /// The `cfg(...)` expression or target triple the dependency is gated on.
+    pub target: Option<String>,
+}
+
+/// Restricts which parts of the resolved graph are analyzed.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only follow edges that are active on at least one of these targets.
+    /// `None` keeps the edges of every platform.
+    pub targets: Option<Vec<Target>>,
+    /// The platform that build scripts, proc-macros and their dependencies are
+    /// compiled for. `None` evaluates them against `targets` as well.
+    pub host: Option<Target>,
+    /// Only follow edges of these kinds. `None` keeps every kind.
+    pub kinds: Option<BTreeSet<DepKind>>,
+    /// Names of the workspace members to start from. Empty means every member.
+    pub packages: Vec<String>,
+}
+
+/// Feature selection passed through to `cargo metadata`, with the same meaning as
+/// the flags of `cargo build`.
+#[derive(Clone, Debug, Default)]
+pub struct Features {
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+}
+
+impl Filter {
+    /// Returns the kinds through which `dep` is still active, which is empty when
+    /// the edge should be dropped. `host` is whether the user is compiled for the
+    /// host.
+    fn kept_kinds(&self, dep: &NodeDep, host: bool) -> Result<BTreeSet<DepKind>> {
+        // Metadata from cargo older than 1.41 has no dep_kinds, so treat the edge as normal
+        if dep.dep_kinds.is_empty() {
+            return Ok(self.kind_allowed(DepKind::Normal).into_iter().collect());
+        }
+        let mut kept = BTreeSet::new();
+        for info in &dep.dep_kinds {
+            let kind = info.kind()?;
+            if self.kind_allowed(kind).is_some() && self.platform_allowed(info, host)? {
+                kept.insert(kind);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn kind_allowed(&self, kind: DepKind) -> Option<DepKind> {
+        match &self.kinds {
+            Some(kinds) if !kinds.contains(&kind) => None,
+            _ => Some(kind),
+        }
+    }
+
+    fn platform_allowed(&self, info: &DepKindInfo, host: bool) -> Result<bool> {
+        let (Some(targets), Some(platform)) = (&self.targets, &info.target) else {
+            return Ok(true);
+        };
+        let targets = match &self.host {
+            Some(target) if host => std::slice::from_ref(target),
+            _ => targets.as_slice(),
+        };
+        for target in targets {
+            if target.matches(platform)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl DepKindInfo {
+    fn kind(&self) -> Result<DepKind> {
+        match self.kind.as_deref() {
+            None => Ok(DepKind::Normal),
+            Some(kind) => kind
+                .parse()
+                .map_err(|_| Error::Metadata(format!("unknown dependency kind `{kind}`"))),
+        }
+    }
+}
+
+impl Metadata {
+    /// Runs `cargo metadata` for the given manifest, or the current directory when `None`.
+    ///
+    /// Cargo only resolves the optional dependencies enabled by `features`, so the
+    /// returned graph matches what a build with the same flags would compile.
+    pub fn from_cargo(manifest_path: Option<&Path>, features: &Features) -> Result<Self> {
+        let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut command = Command::new(cargo);
+        command.args(["metadata", "--format-version", "1"]);
+        if let Some(manifest_path) = manifest_path {
+            command.arg("--manifest-path").arg(manifest_path);
+        }
+        if !features.features.is_empty() {
+            command.arg("--features").arg(features.features.join(","));
+        }
+        if features.all_features {
+            command.arg("--all-features");
+        }
+        if features.no_default_features {
+            command.arg("--no-default-features");
+        }
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(Error::Command {
+                program: "cargo metadata".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Reads previously captured `cargo metadata` JSON from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Converts the resolved nodes into lockfile packages, so that the rest of the
+    /// analysis does not need to know which backend produced them.
+    ///
+    /// Only the edges accepted by `filter` are kept, dev-dependency edges only from
+    /// the members the walk starts from, and packages that can no longer be reached
+    /// from those members are dropped. Like cargo, the walk switches to the host
+    /// platform at build-dependency and proc-macro edges, so everything below them is
+    /// filtered with `filter.host`.
+    pub fn graph(&self, filter: &Filter) -> Result<Graph> {
+        let resolve = self
+            .resolve
+            .as_ref()
+            .ok_or_else(|| Error::Metadata("no dependency resolution was reported".to_string()))?;
+        let by_id: HashMap<&str, &MetadataPackage> = self
+            .packages
+            .iter()
+            .map(|package| (package.id.as_str(), package))
+            .collect();
+        let lookup = |id: &str| {
+            by_id
+                .get(id)
+                .copied()
+                .ok_or_else(|| Error::Metadata(format!("unknown package id `{id}`")))
+        };
+
+        let nodes: HashMap<&str, &Node> = resolve
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node))
+            .collect();
+
+        let roots = self.roots(filter, lookup)?;
+        let selected: HashSet<&str> = roots.iter().copied().collect();
+        // A package is visited once for each platform it is compiled for
+        let mut visited: HashSet<(&str, bool)> = HashSet::new();
+        let mut queue: VecDeque<(&str, bool)> = roots.into_iter().map(|id| (id, false)).collect();
+        let mut packages: HashMap<&str, Package> = HashMap::new();
+        let mut graph = Graph::default();
+        while let Some((id, host)) = queue.pop_front() {
+            if !visited.insert((id, host)) {
+                continue;
+            }
+            let node = nodes
+                .get(id)
+                .ok_or_else(|| Error::Metadata(format!("`{id}` is not in the resolve graph")))?;
+            let package = match packages.entry(id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let package = lookup(id)?.to_package()?;
+                    graph.requirements.insert(
+                        Dependency::from(&package),
+                        lookup(id)?
+                            .dependencies
+                            .iter()
+                            .map(|dep| (dep.name.clone(), dep.req.clone()))
+                            .collect(),
+                    );
+                    entry.insert(package)
+                }
+            };
+            let user = Dependency::from(&*package);
+            for dep in &node.deps {
+                let mut kinds = filter.kept_kinds(dep, host)?;
+                // Like `cargo tree`, only the selected members are built with their
+                // dev-dependencies
+                if !selected.contains(id) {
+                    kinds.remove(&DepKind::Dev);
+                }
+                if kinds.is_empty() {
+                    continue;
+                }
+                let target = lookup(&dep.pkg)?;
+                for kind in &kinds {
+                    let host = host || *kind == DepKind::Build || target.is_proc_macro();
+                    queue.push_back((&dep.pkg, host));
+                }
+                let dependency = Dependency::from(&target.to_package()?);
+                if !package.dependencies.contains(&dependency) {
+                    package.dependencies.push(dependency.clone());
+                }
+                graph
+                    .edge_kinds
+                    .entry((user.clone(), dependency))
+                    .or_default()
+                    .extend(kinds);
+            }
+        }
+        graph.packages = packages.into_values().collect();
+        graph.packages.sort();
+        Ok(graph)
+    }
+}
+
+impl Metadata {
+    /// Picks the workspace members named in `filter.packages`, or all of them.
+    fn roots<'a>(
+        &'a self,
+        filter: &Filter,
+        lookup: impl Fn(&str) -> Result<&'a MetadataPackage>,
+    ) -> Result<Vec<&'a str>> {
+        if filter.packages.is_empty() {
+            return Ok(self.workspace_members.iter().map(String::as_str).collect());
+        }
+        let mut roots = vec![];
+        for name in &filter.packages {
+            let mut found = false;
+            for id in &self.workspace_members {
+                if lookup(id)?.name == *name {
+                    roots.push(id.as_str());
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(Error::InvalidArgument(format!(
+                    "package `{name}` is not a member of the workspace"
+                )));
+            }
+        }
+        Ok(roots)
+    }
+}
+
+impl FromStr for Metadata {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl MetadataPackage {
+    fn is_proc_macro(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.kind.iter().any(|kind| kind == "proc-macro"))
+    }
+
+    fn to_package(&self) -> Result<Package> {
+        Ok(Package {
+            name: self.name.parse()?,
+            version: Version::parse(&self.version)?,
+            source: self.source.as_deref().map(SourceId::from_url).transpose()?,
+            checksum: None,
+            dependencies: vec![],
+            replace: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRY: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    fn package(name: &str, version: &str) -> MetadataPackage {
+        let member = matches!(name, "a" | "b");
+        MetadataPackage {
+            id: format!("{name} {version}"),
+            name: name.to_string(),
+            version: version.to_string(),
+            source: (!member).then(|| REGISTRY.to_string()),
+            manifest_path: PathBuf::from(format!("/ws/{name}/Cargo.toml")),
+            dependencies: vec![],
+            targets: vec![],
+        }
+    }
+
+    fn dep(pkg: &str, kind: Option<&str>, target: Option<&str>) -> NodeDep {
+        NodeDep {
+            name: pkg.split(' ').next().unwrap().to_string(),
+            pkg: pkg.to_string(),
+            dep_kinds: vec![DepKindInfo {
+                kind: kind.map(String::from),
+                target: target.map(String::from),
+            }],
+        }
+    }
+
+    /// Members `a` and `b`, where `a` depends on `b`, on `foo 2.0.0`, on `winapi`
+    /// on Windows only, on `cc` to build and on `tempfile` to test, and `b` uses
+    /// `foo 1.0.0` to test.
+    fn workspace() -> Metadata {
+        let packages = [
+            ("a", "0.1.0"),
+            ("b", "0.1.0"),
+            ("foo", "1.0.0"),
+            ("foo", "2.0.0"),
+            ("winapi", "0.3.9"),
+            ("cc", "1.0.0"),
+            ("tempfile", "3.0.0"),
+        ];
+        let nodes = vec![
+            Node {
+                id: "a 0.1.0".to_string(),
+                deps: vec![
+                    dep("b 0.1.0", None, None),
+                    dep("foo 2.0.0", None, None),
+                    dep("winapi 0.3.9", None, Some("cfg(windows)")),
+                    dep("cc 1.0.0", Some("build"), None),
+                    dep("tempfile 3.0.0", Some("dev"), None),
+                ],
+            },
+            Node {
+                id: "b 0.1.0".to_string(),
+                deps: vec![dep("foo 1.0.0", Some("dev"), None)],
+            },
+        ];
+        Metadata {
+            packages: packages
+                .iter()
+                .map(|(name, version)| package(name, version))
+                .collect(),
+            workspace_members: vec!["a 0.1.0".to_string(), "b 0.1.0".to_string()],
+            workspace_root: PathBuf::from("/ws"),
+            resolve: Some(Resolve {
+                nodes: packages
+                    .iter()
+                    .map(|(name, version)| format!("{name} {version}"))
+                    .filter(|id| !nodes.iter().any(|node| node.id == *id))
+                    .map(|id| Node { id, deps: vec![] })
+                    .chain(nodes.clone())
+                    .collect(),
+                root: None,
+            }),
+        }
+    }
+
+    fn names(filter: &Filter) -> Vec<String> {
+        names_in(&workspace(), filter)
+    }
+
+    fn names_in(metadata: &Metadata, filter: &Filter) -> Vec<String> {
+        metadata
+            .graph(filter)
+            .unwrap()
+            .packages
+            .iter()
+            .map(|package| format!("{} {}", package.name, package.version))
+            .collect()
+    }
+
+    fn target(triple: &str, cfg: &[&str]) -> Target {
+        Target {
+            triple: triple.to_string(),
+            cfg: cfg.iter().map(|cfg| cfg.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn keeps_everything_without_a_filter() {
+        assert_eq!(
+            names(&Filter::default()),
+            [
+                "a 0.1.0",
+                "b 0.1.0",
+                "cc 1.0.0",
+                "foo 1.0.0",
+                "foo 2.0.0",
+                "tempfile 3.0.0",
+                "winapi 0.3.9"
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_dev_dependencies_of_selected_packages_only() {
+        let filter = Filter {
+            packages: vec!["a".to_string()],
+            ..Filter::default()
+        };
+        let kept = names(&filter);
+        assert!(kept.contains(&"tempfile 3.0.0".to_string()));
+        assert!(kept.contains(&"b 0.1.0".to_string()));
+        assert!(!kept.contains(&"foo 1.0.0".to_string()));
+
+        let filter = Filter {
+            packages: vec!["b".to_string()],
+            ..Filter::default()
+        };
+        assert_eq!(names(&filter), ["b 0.1.0", "foo 1.0.0"]);
+    }
+
+    #[test]
+    fn rejects_unknown_packages() {
+        let filter = Filter {
+            packages: vec!["c".to_string()],
+            ..Filter::default()
+        };
+        assert!(workspace().graph(&filter).is_err());
+    }
+
+    #[test]
+    fn filters_edge_kinds() {
+        let filter = Filter {
+            kinds: Some([DepKind::Normal].into()),
+            ..Filter::default()
+        };
+        assert_eq!(
+            names(&filter),
+            ["a 0.1.0", "b 0.1.0", "foo 2.0.0", "winapi 0.3.9"]
+        );
+        let graph = workspace()
+            .graph(&Filter {
+                kinds: Some([DepKind::Build].into()),
+                ..Filter::default()
+            })
+            .unwrap();
+        let kinds: Vec<&BTreeSet<DepKind>> = graph.edge_kinds.values().collect();
+        assert_eq!(kinds, [&BTreeSet::from([DepKind::Build])]);
+    }
+
+    #[test]
+    fn filters_platforms() {
+        let linux = target("x86_64-unknown-linux-gnu", &["unix", "target_os=\"linux\""]);
+        let windows = target(
+            "x86_64-pc-windows-msvc",
+            &["windows", "target_os=\"windows\""],
+        );
+        let filter = Filter {
+            targets: Some(vec![linux.clone()]),
+            ..Filter::default()
+        };
+        assert!(!names(&filter).contains(&"winapi 0.3.9".to_string()));
+        let filter = Filter {
+            targets: Some(vec![linux, windows]),
+            ..Filter::default()
+        };
+        assert!(names(&filter).contains(&"winapi 0.3.9".to_string()));
+    }
+
+    #[test]
+    fn filters_build_dependencies_and_proc_macros_for_the_host() {
+        // `cc` needs `jobserver` on unix, the proc-macro `derive` needs `libc` on
+        // unix, and `a` itself needs `getrandom` on unix
+        let mut metadata = workspace();
+        let mut derive = package("derive", "1.0.0");
+        derive.targets.push(MetadataTarget {
+            kind: vec!["proc-macro".to_string()],
+        });
+        metadata.packages.extend([
+            derive,
+            package("jobserver", "0.1.0"),
+            package("libc", "0.2.0"),
+            package("getrandom", "0.2.0"),
+        ]);
+        let resolve = metadata.resolve.as_mut().unwrap();
+        for node in &mut resolve.nodes {
+            match node.id.as_str() {
+                "a 0.1.0" => node.deps.extend([
+                    dep("derive 1.0.0", None, None),
+                    dep("getrandom 0.2.0", None, Some("cfg(unix)")),
+                ]),
+                "cc 1.0.0" => node
+                    .deps
+                    .push(dep("jobserver 0.1.0", None, Some("cfg(unix)"))),
+                _ => {}
+            }
+        }
+        resolve.nodes.extend([
+            Node {
+                id: "derive 1.0.0".to_string(),
+                deps: vec![dep("libc 0.2.0", None, Some("cfg(unix)"))],
+            },
+            Node {
+                id: "jobserver 0.1.0".to_string(),
+                deps: vec![],
+            },
+            Node {
+                id: "libc 0.2.0".to_string(),
+                deps: vec![],
+            },
+            Node {
+                id: "getrandom 0.2.0".to_string(),
+                deps: vec![],
+            },
+        ]);
+
+        let wasm = target(
+            "wasm32-unknown-unknown",
+            &["target_family=\"wasm\"", "target_arch=\"wasm32\""],
+        );
+        let linux = target("x86_64-unknown-linux-gnu", &["unix", "target_os=\"linux\""]);
+        let filter = Filter {
+            targets: Some(vec![wasm]),
+            host: Some(linux),
+            ..Filter::default()
+        };
+        let kept = names_in(&metadata, &filter);
+        assert!(kept.contains(&"jobserver 0.1.0".to_string()));
+        assert!(kept.contains(&"libc 0.2.0".to_string()));
+        assert!(!kept.contains(&"getrandom 0.2.0".to_string()));
+
+        let filter = Filter {
+            host: None,
+            ..filter
+        };
+        let kept = names_in(&metadata, &filter);
+        assert!(!kept.contains(&"jobserver 0.1.0".to_string()));
+        assert!(!kept.contains(&"libc 0.2.0".to_string()));
+    }
+}
@@ -0,0 +1,325 @@
+//! Turns the duplicate list into `cargo update --precise` commands.
+
+use crate::graph::{find_info, Graph, PackageMap};
+use crate::kind::DuplicateKind;
+use crate::manifest::read_requirements;
+use crate::registry::Index;
+use crate::upgrade::Fix;
+use crate::Duplicate;
+use cargo_lock::{Dependency, Package, SourceId};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// One `cargo update -p package@from --precise to` command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Step {
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    /// The duplicate this step removes, as `name version`.
+    pub removes: String,
+    pub command: String,
+}
+
+/// A candidate step that was not suggested, and why.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Skipped {
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    pub skipped: Vec<Skipped>,
+}
+
+impl Plan {
+    /// Renders the steps as a POSIX shell script.
+    pub fn to_script(&self) -> String {
+        let mut script = String::from("#!/bin/sh\nset -e\n");
+        for step in &self.steps {
+            script.push_str(&format!("# removes {}\n{}\n", step.removes, step.command));
+        }
+        script
+    }
+}
+
+struct Candidate {
+    id: Dependency,
+    to: Version,
+    removes: String,
+}
+
+/// Builds an ordered fix plan.
+///
+/// Semver compatible duplicates are moved onto their canonical version, and users
+/// whose verdict is a semver compatible upgrade are bumped. Upgrades come first,
+/// since they change which versions the remaining steps have to satisfy. Every step
+/// is checked against the requirements of all users of the package it changes, with
+/// the manifests of members and other path packages looked up under
+/// `workspace_root`.
+///
+/// Upgrade candidates come from [`Duplicate::verdicts`], so the duplicates should
+/// have been found with [`crate::Options::check_upgrades`] set.
+pub async fn build_plan(
+    duplicates: &[Duplicate],
+    package_map: &PackageMap,
+    graph: &Graph,
+    index: &mut Index,
+    workspace_root: &Path,
+) -> Plan {
+    // Keyed by package id, so several duplicates asking for the same bump share a step
+    let mut upgrades: BTreeMap<(String, Version), Candidate> = BTreeMap::new();
+    let mut unifications = vec![];
+    for duplicate in duplicates {
+        let Some(canonical) = &duplicate.canonical else {
+            continue;
+        };
+        let removes = format!("{} {}", duplicate.package, duplicate.version);
+        if duplicate.kind == DuplicateKind::Compatible {
+            if let (Ok(version), Ok(canonical), Ok(name)) = (
+                Version::parse(&duplicate.version),
+                Version::parse(canonical),
+                duplicate.package.parse(),
+            ) {
+                unifications.push(Candidate {
+                    id: Dependency {
+                        name,
+                        version,
+                        source: duplicate.source.clone(),
+                    },
+                    to: canonical,
+                    removes,
+                });
+            }
+            continue;
+        }
+        for verdict in &duplicate.verdicts {
+            let Fix::Upgrade { to } = &verdict.fix else {
+                continue;
+            };
+            let Some(user) = duplicate.users.iter().find(|user| {
+                user.name.as_str() == verdict.user && user.version.to_string() == verdict.version
+            }) else {
+                continue;
+            };
+            let Ok(to) = Version::parse(to) else {
+                continue;
+            };
+            // A breaking upgrade needs a manifest change, which `cargo update` cannot do
+            if DuplicateKind::classify(&user.version, &to) != DuplicateKind::Compatible {
+                continue;
+            }
+            let key = (user.name.to_string(), user.version.clone());
+            match upgrades.get(&key) {
+                Some(existing) if existing.to >= to => {}
+                _ => {
+                    upgrades.insert(
+                        key,
+                        Candidate {
+                            id: Dependency::from(user),
+                            to,
+                            removes: removes.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    let mut plan = Plan::default();
+    for candidate in upgrades.into_values().chain(unifications) {
+        let package = candidate.id.name.to_string();
+        let from = candidate.id.version.to_string();
+        let to = candidate.to.to_string();
+        let checked = check(
+            package_map,
+            graph,
+            index,
+            workspace_root,
+            &candidate.id,
+            &candidate.to,
+        );
+        match checked.await {
+            Ok(()) => plan.steps.push(Step {
+                command: format!("cargo update -p {package}@{from} --precise {to}"),
+                package,
+                from,
+                to,
+                removes: candidate.removes,
+            }),
+            Err(reason) => plan.skipped.push(Skipped {
+                package,
+                from,
+                to,
+                reason,
+            }),
+        }
+    }
+    plan
+}
+
+/// Checks that every user of `id` accepts `to`, returning the reason when one does not.
+async fn check(
+    package_map: &PackageMap,
+    graph: &Graph,
+    index: &mut Index,
+    workspace_root: &Path,
+    id: &Dependency,
+    to: &Version,
+) -> Result<(), String> {
+    let Some(info) = find_info(package_map, id) else {
+        return Err(format!("{} {} is not in the graph", id.name, id.version));
+    };
+    for user in &info.users {
+        let Some(reqs) = requirements(graph, index, workspace_root, user, id.name.as_str()).await
+        else {
+            return Err(format!(
+                "the requirements of {} {} could not be read",
+                user.name, user.version
+            ));
+        };
+        if let Some(req) = reqs.iter().find(|req| !req.matches(to)) {
+            return Err(format!(
+                "{} {} requires {} {req}",
+                user.name, user.version, id.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The requirements `user` places on `package`, read from the graph when the backend
+/// recorded them, from the registry index for crates.io packages, and from the
+/// manifest on disk otherwise.
+pub async fn requirements(
+    graph: &Graph,
+    index: &mut Index,
+    workspace_root: &Path,
+    user: &Package,
+    package: &str,
+) -> Option<Vec<VersionReq>> {
+    if let Some(requirements) = graph.requirements.get(&Dependency::from(user)) {
+        return Some(
+            requirements
+                .iter()
+                .filter(|(name, _)| name == package)
+                .map(|(_, req)| req.clone())
+                .collect(),
+        );
+    }
+    if !user
+        .source
+        .as_ref()
+        .is_some_and(SourceId::is_default_registry)
+    {
+        return read_requirements(user, package, workspace_root).ok();
+    }
+    let entries = index.entries(user.name.as_str()).await.ok()?;
+    let entry = entries.iter().find(|entry| entry.vers == user.version)?;
+    Some(
+        entry
+            .dependencies_on(package)
+            .map(|dep| dep.req.clone())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::build_package_map;
+    use crate::registry::new_client;
+    use crate::{find_duplicates, Options};
+    use cargo_lock::Lockfile;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    const LOCKFILE: &str = r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "foo 1.0.0",
+]
+
+[[package]]
+name = "foo"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "foo"
+version = "1.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "lib"
+version = "0.1.0"
+dependencies = [
+ "foo 1.2.0",
+]
+"#;
+
+    /// Writes a workspace whose `app` member depends on `foo` with `app_dep`, and
+    /// whose `[workspace.dependencies]` pins `foo = "=1.0.0"`.
+    fn workspace(test: &str, app_dep: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "cargo-duplicated-deps-plan-{test}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nfoo = \"=1.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("app").join("Cargo.toml"),
+            format!("[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = {app_dep}\n"),
+        )
+        .unwrap();
+        root
+    }
+
+    async fn plan(test: &str, app_dep: &str) -> Plan {
+        let lockfile = Lockfile::from_str(LOCKFILE).unwrap();
+        let graph = Graph::from(&lockfile);
+        let package_map = build_package_map(&graph).unwrap();
+        let options = Options {
+            offline: true,
+            ..Options::default()
+        };
+        let duplicates = find_duplicates(&package_map, &options).await.unwrap();
+        let mut index = Index::new(new_client().unwrap(), true);
+        let root = workspace(test, app_dep);
+        let plan = build_plan(&duplicates, &package_map, &graph, &mut index, &root).await;
+        std::fs::remove_dir_all(root).unwrap();
+        plan
+    }
+
+    #[tokio::test]
+    async fn suggests_a_step_the_member_allows() {
+        let plan = plan("allow", "\"1\"").await;
+        assert!(plan.skipped.is_empty());
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(
+            plan.steps[0].command,
+            "cargo update -p foo@1.0.0 --precise 1.2.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_a_step_an_inherited_requirement_excludes() {
+        let plan = plan("inherit", "{ workspace = true }").await;
+        assert!(plan.steps.is_empty());
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].reason, "app 0.1.0 requires foo =1.0.0");
+    }
+}
@@ -0,0 +1,113 @@
+//! Rules that decide whether the reported duplicates should fail a build.
+
+use crate::baseline::{Baseline, BaselineEntry};
+use crate::kind::DuplicateKind;
+use crate::Duplicate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+/// Which duplicates are acceptable. The default allows everything.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// Fail on any duplicate.
+    pub deny: bool,
+    /// Fail on more duplicates than this.
+    pub max_duplicates: Option<usize>,
+    /// Fail on a duplicate of one of these kinds.
+    pub deny_kinds: BTreeSet<DuplicateKind>,
+    /// Known duplicates, which the other rules leave out and which are not a
+    /// violation themselves. Any other duplicate is.
+    pub baseline: Option<Baseline>,
+}
+
+/// A broken rule of a [`Policy`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+pub enum Violation {
+    NotInBaseline {
+        package: String,
+        version: String,
+    },
+    Denied {
+        count: usize,
+    },
+    TooMany {
+        count: usize,
+        max: usize,
+    },
+    DeniedKind {
+        package: String,
+        version: String,
+        kind: DuplicateKind,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::NotInBaseline { package, version } => {
+                write!(f, "{package} v{version} is not in the baseline")
+            }
+            Violation::Denied { count } => write!(f, "found {count} duplicates"),
+            Violation::TooMany { count, max } => {
+                write!(f, "found {count} duplicates, more than the {max} allowed")
+            }
+            Violation::DeniedKind {
+                package,
+                version,
+                kind,
+            } => write!(f, "{package} v{version} is a denied {kind} duplicate"),
+        }
+    }
+}
+
+/// The outcome of [`Policy::check`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PolicyReport {
+    pub violations: Vec<Violation>,
+    /// Baseline entries that are no longer duplicated.
+    pub fixed: Vec<BaselineEntry>,
+}
+
+impl Policy {
+    /// Checks every reported duplicate, so it should run before any `--top` style
+    /// truncation.
+    pub fn check(&self, duplicates: &[Duplicate]) -> PolicyReport {
+        let mut report = PolicyReport::default();
+        let mut checked: Vec<&Duplicate> = duplicates.iter().collect();
+        if let Some(baseline) = &self.baseline {
+            report.fixed = baseline.fixed(duplicates);
+            checked.retain(|duplicate| !baseline.contains(duplicate));
+            for duplicate in &checked {
+                report.violations.push(Violation::NotInBaseline {
+                    package: duplicate.package.clone(),
+                    version: duplicate.version.clone(),
+                });
+            }
+        }
+        if self.deny && !checked.is_empty() {
+            report.violations.push(Violation::Denied {
+                count: checked.len(),
+            });
+        }
+        if let Some(max) = self.max_duplicates {
+            if checked.len() > max {
+                report.violations.push(Violation::TooMany {
+                    count: checked.len(),
+                    max,
+                });
+            }
+        }
+        for duplicate in &checked {
+            if self.deny_kinds.contains(&duplicate.kind) {
+                report.violations.push(Violation::DeniedKind {
+                    package: duplicate.package.clone(),
+                    version: duplicate.version.clone(),
+                    kind: duplicate.kind,
+                });
+            }
+        }
+        report
+    }
+}
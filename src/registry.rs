@@ -0,0 +1,235 @@
+//! Access to crates.io: the web API for newest versions, and the sparse index for
+//! the dependencies of every published release.
+
+use crate::error::{Error, Result};
+use reqwest::{Client, StatusCode};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const SPARSE_INDEX: &str = "https://index.crates.io";
+
+pub fn new_client() -> Result<Client> {
+    Ok(Client::builder()
+        .user_agent("cargo-duplicated-deps")
+        .build()?)
+}
+
+pub async fn get_latest_version(client: &Client, package: &str) -> Result<String> {
+    let url = format!("https://crates.io/api/v1/crates/{package}");
+    let response = client.execute(client.get(&url).build()?).await?;
+    let json: serde_json::Value = response.json().await?;
+    let latest_version = json["crate"]["newest_version"]
+        .as_str()
+        .ok_or_else(|| Error::NoLatestVersion(package.to_string()))?;
+    Ok(latest_version.to_string())
+}
+
+/// One published release of a crate, as listed in the registry index.
+#[derive(Clone, Debug, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub vers: Version,
+    #[serde(default)]
+    pub deps: Vec<IndexDep>,
+    #[serde(default)]
+    pub yanked: bool,
+}
+
+/// A dependency declared by a published release.
+#[derive(Clone, Debug, Deserialize)]
+pub struct IndexDep {
+    /// The name the dependency is used under, which differs from the crate name
+    /// when it is renamed.
+    pub name: String,
+    pub req: VersionReq,
+    #[serde(default)]
+    pub optional: bool,
+    /// `None` for normal dependencies, otherwise `"dev"` or `"build"`.
+    pub kind: Option<String>,
+    /// The real crate name, for renamed dependencies.
+    pub package: Option<String>,
+}
+
+impl IndexEntry {
+    /// Finds the non-dev dependencies on `package`, accounting for renames.
+    pub fn dependencies_on<'a>(&'a self, package: &'a str) -> impl Iterator<Item = &'a IndexDep> {
+        self.deps.iter().filter(move |dep| {
+            dep.package.as_deref().unwrap_or(&dep.name) == package
+                && dep.kind.as_deref() != Some("dev")
+        })
+    }
+}
+
+/// Reads crate releases from the crates.io sparse index, falling back to the copy
+/// cargo keeps in `$CARGO_HOME/registry/index`.
+pub struct Index {
+    client: Client,
+    offline: bool,
+    entries: HashMap<String, Vec<IndexEntry>>,
+}
+
+impl Index {
+    /// With `offline`, only the local cargo cache is consulted.
+    pub fn new(client: Client, offline: bool) -> Self {
+        Index {
+            client,
+            offline,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Every release of `package`, oldest first.
+    pub async fn entries(&mut self, package: &str) -> Result<&[IndexEntry]> {
+        let package = package.to_lowercase();
+        if !self.entries.contains_key(&package) {
+            let fetched = if self.offline {
+                None
+            } else {
+                self.fetch(&package).await.ok()
+            };
+            let mut entries = match fetched {
+                Some(entries) => entries,
+                None => read_local_cache(&package)?,
+            };
+            entries.sort_by(|a, b| a.vers.cmp(&b.vers));
+            self.entries.insert(package.clone(), entries);
+        }
+        Ok(&self.entries[&package])
+    }
+
+    async fn fetch(&self, package: &str) -> Result<Vec<IndexEntry>> {
+        let url = format!("{SPARSE_INDEX}/{}", index_path(package));
+        let response = self.client.execute(self.client.get(&url).build()?).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotInIndex(package.to_string()));
+        }
+        let body = response.error_for_status()?.text().await?;
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+}
+
+/// The path of a crate's file within the index, such as `se/rd/serde`.
+fn index_path(package: &str) -> String {
+    match package.len() {
+        1 => format!("1/{package}"),
+        2 => format!("2/{package}"),
+        3 => format!("3/{}/{package}", &package[..1]),
+        _ => format!("{}/{}/{package}", &package[..2], &package[2..4]),
+    }
+}
+
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+}
+
+/// Reads the index cache cargo writes for the sparse crates.io registry.
+fn read_local_cache(package: &str) -> Result<Vec<IndexEntry>> {
+    let not_found = || Error::NotInIndex(package.to_string());
+    let index_dir = cargo_home()
+        .ok_or_else(not_found)?
+        .join("registry")
+        .join("index");
+    for dir in std::fs::read_dir(index_dir).map_err(|_| not_found())? {
+        let dir = dir?;
+        if !dir
+            .file_name()
+            .to_string_lossy()
+            .starts_with("index.crates.io-")
+        {
+            continue;
+        }
+        let Ok(data) = std::fs::read(dir.path().join(".cache").join(index_path(package))) else {
+            continue;
+        };
+        if let Some(entries) = parse_cache(&data) {
+            return entries;
+        }
+    }
+    Err(not_found())
+}
+
+/// Parses one file of the index cache, or returns `None` when it is too short to
+/// hold the header.
+///
+/// Each file starts with a cache version byte, a little endian `u32` index version
+/// and a NUL terminated revision, followed by NUL terminated pairs of version
+/// string and JSON entry.
+fn parse_cache(data: &[u8]) -> Option<Result<Vec<IndexEntry>>> {
+    let fields: Vec<&[u8]> = data.get(5..)?.split(|byte| *byte == 0).skip(1).collect();
+    Some(
+        fields
+            .chunks_exact(2)
+            .map(|pair| Ok(serde_json::from_slice(pair[1])?))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cache file as cargo writes it, for the given `(version, JSON)` pairs.
+    fn cache(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = vec![3];
+        data.extend(2u32.to_le_bytes());
+        data.extend(b"etag: \"abc\"\0");
+        for (version, json) in entries {
+            data.extend(version.as_bytes());
+            data.push(0);
+            data.extend(json.as_bytes());
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn parses_cached_entries() {
+        let data = cache(&[
+            (
+                "1.0.0",
+                r#"{"name":"foo","vers":"1.0.0","deps":[],"yanked":false}"#,
+            ),
+            (
+                "1.1.0",
+                r#"{"name":"foo","vers":"1.1.0","deps":[{"name":"bar","req":"^0.2","optional":false,"kind":null,"package":null}],"yanked":true}"#,
+            ),
+        ]);
+        let entries = parse_cache(&data).unwrap().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].vers, Version::new(1, 0, 0));
+        assert!(!entries[0].yanked);
+        assert!(entries[1].yanked);
+        assert_eq!(entries[1].deps[0].req, "^0.2".parse().unwrap());
+    }
+
+    #[test]
+    fn parses_a_cache_without_entries() {
+        assert!(parse_cache(&cache(&[])).unwrap().unwrap().is_empty());
+    }
+
+    #[test]
+    fn skips_a_truncated_header() {
+        assert!(parse_cache(&[3, 2, 0]).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let data = cache(&[("1.0.0", "{not json")]);
+        assert!(parse_cache(&data).unwrap().is_err());
+    }
+
+    #[test]
+    fn lays_out_index_paths() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("syn"), "3/s/syn");
+        assert_eq!(index_path("serde"), "se/rd/serde");
+    }
+}
@@ -0,0 +1,157 @@
+//! SARIF 2.1.0 output, for code scanning dashboards.
+
+use crate::graph::{get_usage_chain, PackageMap};
+use crate::kind::DuplicateKind;
+use crate::Duplicate;
+use cargo_lock::SourceId;
+use serde_json::{json, Value};
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+/// The base of relative artifact locations, which code scanning takes to be the root
+/// of the repository.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// The SARIF rule reporting duplicates of `kind`.
+pub fn rule_id(kind: DuplicateKind) -> String {
+    format!("duplicate-{kind}")
+}
+
+fn rule_description(kind: DuplicateKind) -> &'static str {
+    match kind {
+        DuplicateKind::Compatible => {
+            "Semver compatible duplicate, which `cargo update` can usually unify"
+        }
+        DuplicateKind::Major => "Duplicate with a different major version",
+        DuplicateKind::ZeroMinor => "Duplicate with a different minor version of a 0.x crate",
+        DuplicateKind::Prerelease => "Duplicate where one of the versions is a prerelease",
+        DuplicateKind::DifferentSource => "The same version of a crate from a different source",
+    }
+}
+
+fn level(kind: DuplicateKind) -> &'static str {
+    match kind {
+        DuplicateKind::Compatible => "note",
+        _ => "warning",
+    }
+}
+
+/// Finds the 1-based line of the `[[package]]` entry for a copy of a crate in the
+/// text of a lockfile. When several entries share the name and version, the one with
+/// a matching `source` wins.
+pub fn package_line(lockfile: &str, duplicate: &Duplicate) -> Option<usize> {
+    let lines: Vec<&str> = lockfile.lines().collect();
+    let name = format!("name = \"{}\"", duplicate.package);
+    let version = format!("version = \"{}\"", duplicate.version);
+    let mut found = None;
+    for (i, line) in lines.iter().enumerate() {
+        if *line != "[[package]]" {
+            continue;
+        }
+        let entry: Vec<&str> = lines[i + 1..]
+            .iter()
+            .take_while(|line| !line.is_empty() && **line != "[[package]]")
+            .copied()
+            .collect();
+        if !entry.contains(&name.as_str()) || !entry.contains(&version.as_str()) {
+            continue;
+        }
+        // Parsed rather than compared as text, since the lockfile keeps the `#<rev>`
+        // of git sources that their `Display` leaves out. Both sides are normalized
+        // the way package ids in the graph are.
+        let source = entry.iter().find_map(|line| {
+            let url = line.strip_prefix("source = \"")?.strip_suffix('"')?;
+            Some(SourceId::from_url(url).ok())
+        });
+        let same_source = match source {
+            Some(Some(source)) => duplicate.source.as_ref().is_some_and(|expected| {
+                source.normalize_git_source_for_dependency()
+                    == expected.normalize_git_source_for_dependency()
+            }),
+            Some(None) => false,
+            None => duplicate.source.is_none(),
+        };
+        if same_source {
+            return Some(i + 1);
+        }
+        found.get_or_insert(i + 1);
+    }
+    found
+}
+
+/// Builds a SARIF log with one result per duplicate, located at its entry in the
+/// lockfile at `uri`, which is taken relative to the repository root unless it is
+/// an absolute path or a URI such as `file:///...`. `lockfile` is the text of that
+/// file, used to find the line of each entry; without it, results point at the file
+/// as a whole.
+pub fn to_sarif(
+    duplicates: &[Duplicate],
+    package_map: &PackageMap,
+    uri: &str,
+    lockfile: Option<&str>,
+    chain_root: Option<&str>,
+) -> Value {
+    let rules: Vec<Value> = DuplicateKind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": rule_id(*kind),
+                "name": format!("{kind} duplicate"),
+                "shortDescription": { "text": rule_description(*kind) },
+                "defaultConfiguration": { "level": level(*kind) },
+            })
+        })
+        .collect();
+    let mut artifact = json!({ "uri": uri });
+    if !Path::new(uri).is_absolute() && !uri.contains("://") {
+        artifact["uriBaseId"] = json!(SRCROOT);
+    }
+    let results: Vec<Value> = duplicates
+        .iter()
+        .map(|duplicate| {
+            let chains: Vec<String> = duplicate
+                .users
+                .iter()
+                .filter_map(|user| get_usage_chain(package_map, user, chain_root))
+                .collect();
+            let mut message = format!(
+                "{} v{} is duplicated ({})",
+                duplicate.package, duplicate.version, duplicate.kind
+            );
+            if let Some(canonical) = &duplicate.canonical {
+                message.push_str(&format!(", keeping v{canonical}"));
+            }
+            if !chains.is_empty() {
+                message.push_str(&format!(". Used by: {}", chains.join("; ")));
+            }
+            let mut location = json!({ "artifactLocation": artifact });
+            if let Some(line) = lockfile.and_then(|text| package_line(text, duplicate)) {
+                location["region"] = json!({ "startLine": line });
+            }
+            json!({
+                "ruleId": rule_id(duplicate.kind),
+                "ruleIndex": DuplicateKind::ALL
+                    .iter()
+                    .position(|kind| *kind == duplicate.kind),
+                "level": level(duplicate.kind),
+                "message": { "text": message },
+                "locations": [{ "physicalLocation": location }],
+            })
+        })
+        .collect();
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
@@ -0,0 +1,114 @@
+//! Decides, for each user of a duplicate, whether upgrading that user would remove it.
+
+use crate::registry::Index;
+use cargo_lock::Package;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// What can be done about one user of a duplicated version.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Verdict {
+    pub user: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub fix: Fix,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "kebab-case")]
+pub enum Fix {
+    /// The user's current release already accepts the canonical version, so
+    /// `cargo update` can unify the two.
+    Update,
+    /// A newer release of the user depends on the canonical version, or no longer
+    /// depends on the crate at all.
+    Upgrade { to: String },
+    /// No release of the user depends on the canonical version yet.
+    Stuck,
+    /// The user is not published on crates.io, such as a workspace member or a git
+    /// dependency, so its own manifest has to change.
+    NotPublished,
+    /// The registry index could not be read.
+    Unknown { reason: String },
+}
+
+impl Verdict {
+    /// Describes the verdict for a user of `package` at `version`.
+    pub fn describe(&self, package: &str, version: &str, canonical: &str) -> String {
+        let user = &self.user;
+        match &self.fix {
+            Fix::Update => format!(
+                "{user} {} already accepts {package} {canonical}, so `cargo update` can drop {package} {version}",
+                self.version
+            ),
+            Fix::Upgrade { to } => format!(
+                "upgrade {user} {} -> {to} to drop {package} {version}",
+                self.version
+            ),
+            Fix::Stuck => format!("no release of {user} uses {package} {canonical} yet"),
+            Fix::NotPublished => format!(
+                "{user} is not published on crates.io, so change its requirement on {package} directly"
+            ),
+            Fix::Unknown { reason } => format!("could not check {user}: {reason}"),
+        }
+    }
+}
+
+/// Looks through the published releases of `user` for one that depends on
+/// `package` at `canonical`.
+pub async fn check_user(
+    index: &mut Index,
+    package: &str,
+    canonical: &Version,
+    user: &Package,
+) -> Verdict {
+    let verdict = |fix| Verdict {
+        user: user.name.to_string(),
+        version: user.version.to_string(),
+        fix,
+    };
+    if !user
+        .source
+        .as_ref()
+        .is_some_and(|source| source.is_default_registry())
+    {
+        return verdict(Fix::NotPublished);
+    }
+    let entries = match index.entries(user.name.as_str()).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            return verdict(Fix::Unknown {
+                reason: e.to_string(),
+            })
+        }
+    };
+
+    let accepts_canonical = |entry: &crate::registry::IndexEntry| {
+        let mut deps = entry.dependencies_on(package).peekable();
+        deps.peek().is_none() || deps.any(|dep| dep.req.matches(canonical))
+    };
+    if entries
+        .iter()
+        .find(|entry| entry.vers == user.version)
+        .is_some_and(|entry| {
+            entry
+                .dependencies_on(package)
+                .any(|dep| dep.req.matches(canonical))
+        })
+    {
+        return verdict(Fix::Update);
+    }
+    // Entries are sorted, so the first match is the smallest upgrade that helps
+    let upgrade = entries.iter().find(|entry| {
+        entry.vers > user.version
+            && !entry.yanked
+            && (entry.vers.pre.is_empty() || !user.version.pre.is_empty())
+            && accepts_canonical(entry)
+    });
+    match upgrade {
+        Some(entry) => verdict(Fix::Upgrade {
+            to: entry.vers.to_string(),
+        }),
+        None => verdict(Fix::Stuck),
+    }
+}